@@ -1,8 +1,17 @@
+use crate::logging::{LogTimeFormat, LogTimezone, MAX_LOG_BUFFER_LINES};
+use crate::notify::validate_template;
+use crate::parse::ParseOptions;
+use crate::podman::{ComposeTool, RemoteTls, UpOptions, rootless_socket_url};
+
 use anyhow::{Context, Result};
-use clap::Parser;
-use serde::Deserialize;
+use clap::{ArgGroup, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 // =============================================================================
 // Configuration and CLI
@@ -11,28 +20,620 @@ use std::path::{Path, PathBuf};
 #[derive(Parser)]
 #[command(name = "rusty-podmon")]
 #[command(about = "A monitor for Podman containers managed via compose files")]
+#[command(group(ArgGroup::new("discovery_dump").args(["once_per_file", "list_containers"])))]
 pub struct Args {
-    #[arg(short, long, default_value = "monitor.toml")]
-    pub config: PathBuf,
+    /// Config file: TOML, YAML or JSON, chosen by extension
+    /// [default: monitor.toml, unless --compose-file is given]
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Compose file to monitor, in addition to the config file's
+    /// compose_files (repeatable). Without --config, no config file is read
+    /// and every other setting keeps its default
+    #[arg(long = "compose-file", value_name = "PATH")]
+    pub compose_files: Vec<PathBuf>,
 
     #[arg(short, long, default_value = "logs")]
     pub log_dir: PathBuf,
 
+    /// Log to stdout only, without the daily log file
+    #[arg(long)]
+    pub no_log_file: bool,
+
     #[arg(short = 'v', long, default_value = "info")]
     pub log_level: String,
+
+    /// Level for stdout, e.g. "warn" to keep the console quiet while the
+    /// log file gets --log-level [default: --log-level, or warn with
+    /// --detach]
+    #[arg(long)]
+    pub console_log_level: Option<String>,
+
+    /// Plain stdout output even on a terminal (also set by NO_COLOR)
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Log timestamp format: "rfc3339" or a strftime pattern such as
+    /// "%Y-%m-%d %H:%M:%S.%3f" (%Y %y %m %b %B %d %e %j %a %A %H %I %p %M %S
+    /// %f %3f %6f %9f %z %:z %s %F %T %%)
+    #[arg(long, default_value = "rfc3339")]
+    pub log_time_format: LogTimeFormat,
+
+    /// Timezone of log timestamps: "utc", "local" (the host's offset at
+    /// startup) or a fixed offset such as "+02:00"
+    #[arg(long, default_value = "utc")]
+    pub log_timezone: LogTimezone,
+
+    /// Print the effective configuration, defaults included, as TOML and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Check the configuration and every compose file it lists, then exit:
+    /// 0 all good, 1 config error, 2 missing compose file, 3 compose parse
+    /// error (the lowest code wins when several apply)
+    #[arg(long, visible_alias = "config-test")]
+    pub validate: bool,
+
+    /// Print, per compose file, every service the parser saw, its resolved
+    /// container names and restart policy, and why it was included or
+    /// skipped, then exit
+    #[arg(long)]
+    pub once_per_file: bool,
+
+    /// Run discovery once, print each managed container with its compose
+    /// file, then exit
+    #[arg(long)]
+    pub list_containers: bool,
+
+    /// With --once-per-file or --list-containers, print JSON instead of text
+    #[arg(long, requires = "discovery_dump")]
+    pub json: bool,
+
+    /// Fork into the background, appending stdout/stderr (warnings and
+    /// errors, unless --console-log-level says otherwise) to
+    /// <log-dir>/rusty-podmon.out, or discarding them with --no-log-file, and
+    /// write a PID file
+    #[arg(long)]
+    pub detach: bool,
+
+    /// PID file written by --detach and read by `stop`
+    #[arg(long, default_value = "rusty-podmon.pid")]
+    pub pid_file: PathBuf,
+
+    /// Lock file held while monitoring, so a second instance started with
+    /// the same config exits instead of competing for restarts
+    /// [default: <config>.lock]
+    #[arg(long)]
+    pub lock_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    pub fn config_source(&self) -> ConfigSource {
+        let file = match (&self.config, self.compose_files.is_empty()) {
+            (Some(config), _) => Some(config.clone()),
+            (None, true) => Some(PathBuf::from(DEFAULT_CONFIG_FILE)),
+            (None, false) => None,
+        };
+        ConfigSource {
+            file,
+            compose_files: self.compose_files.clone(),
+        }
+    }
+
+    pub fn lock_file(&self) -> PathBuf {
+        self.lock_file.clone().unwrap_or_else(|| {
+            let config = self
+                .config
+                .as_deref()
+                .unwrap_or(Path::new(DEFAULT_CONFIG_FILE));
+            let mut path = config.as_os_str().to_owned();
+            path.push(".lock");
+            path.into()
+        })
+    }
+}
+
+const DEFAULT_CONFIG_FILE: &str = "monitor.toml";
+
+/// Where the configuration is read from: a config file, compose files given
+/// with `--compose-file`, or both (the CLI files are appended).
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    pub file: Option<PathBuf>,
+    pub compose_files: Vec<PathBuf>,
+}
+
+impl From<PathBuf> for ConfigSource {
+    fn from(path: PathBuf) -> Self {
+        Self {
+            file: Some(path),
+            compose_files: Vec::new(),
+        }
+    }
+}
+
+impl ConfigSource {
+    pub fn load(&self) -> Result<Config> {
+        let mut config = match &self.file {
+            Some(path) => Config::from_file(path)?,
+            None => Config::defaults()?,
+        };
+        for path in &self.compose_files {
+            let path = path.display().to_string();
+            if !config.compose_files.iter().any(|entry| entry.path == path) {
+                config
+                    .compose_files
+                    .push(ComposeFileEntry::Path(path).into());
+            }
+        }
+        if config.compose_files.is_empty() {
+            anyhow::bail!(
+                "No compose files to monitor: list them in compose_files of {} or pass --compose-file",
+                self.file
+                    .as_deref()
+                    .unwrap_or(Path::new(DEFAULT_CONFIG_FILE))
+                    .display()
+            );
+        }
+        Ok(config)
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.compose_files.len()) {
+            (Some(path), 0) => write!(f, "{}", path.display()),
+            (Some(path), n) => write!(f, "{} + {} --compose-file", path.display(), n),
+            (None, n) => write!(f, "defaults + {} --compose-file", n),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Show what a running monitor knows about one container, queried
+    /// through its control server (control_listen)
+    Inspect {
+        container: String,
+
+        /// Print the raw JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Ask a running monitor whether it would restart one container right
+    /// now, and if not, why
+    Explain { container: String },
+    /// Stop a monitor started with --detach by sending SIGTERM to the PID in
+    /// --pid-file
+    Stop,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
-    pub compose_files: Vec<String>,
+    pub compose_files: Vec<ComposeFile>,
+    pub compose_command: Option<ComposeTool>,
+    #[serde(default)]
+    pub compose_extra_args: Vec<String>,
+    #[serde(default)]
+    pub pod_restarts: bool,
+    #[serde(default)]
+    pub restart_strategy: RestartStrategy,
+    #[serde(default)]
+    pub restart_force_recreate: bool,
+    #[serde(default)]
+    pub restart_pull_always: bool,
+    pub stop_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub mode: Mode,
+    #[serde(default = "default_enabled")]
+    pub enable_restarts: bool,
+    #[serde(default = "default_enabled")]
+    pub enable_status_reports: bool,
     #[serde(default = "default_check_interval")]
     pub check_interval_seconds: u64,
+    #[serde(default)]
+    pub watch_events: bool,
     #[serde(default = "default_status_interval")]
     pub status_interval_seconds: u64,
     #[serde(default = "default_max_failures")]
     pub max_consecutive_failures: u32,
+    #[serde(default = "default_down_confirmations")]
+    pub down_confirmations: u32,
+    #[serde(default)]
+    pub min_stack_restart_interval_seconds: u64,
+    #[serde(default)]
+    pub restart_spacing_seconds: u64,
+    #[serde(default)]
+    pub respect_restart_policy: bool,
+    #[serde(default)]
+    pub max_giveups: u32,
+    pub min_running_percent: Option<f64>,
+    #[serde(default)]
+    pub quiet_hours: Vec<QuietWindow>,
+    #[serde(default)]
+    pub quiet_hours_utc_offset: UtcOffsetMinutes,
+    #[serde(default = "default_name_template")]
+    pub name_template: String,
+    #[serde(default)]
+    pub name_matching: NameMatching,
+    #[serde(default = "default_enabled")]
+    pub merge_override_files: bool,
+    #[serde(default)]
+    pub label_matching: bool,
+    #[serde(default = "default_restart_attempts")]
+    pub restart_attempts: u32,
+    #[serde(default = "default_restart_retry_delay")]
+    pub restart_retry_delay_seconds: u64,
+    #[serde(default)]
+    pub startup_grace_seconds: u64,
+    #[serde(default)]
+    pub startup_action: StartupAction,
+    #[serde(default = "default_enabled")]
+    pub startup_recovery: bool,
+    #[serde(default)]
+    pub missing_compose_file_prune_seconds: u64,
+    pub maintenance_file: Option<PathBuf>,
+    #[serde(default)]
+    pub maintenance_max_seconds: u64,
+    pub max_load_average: Option<f64>,
+    pub min_available_memory_mb: Option<u64>,
+    #[serde(default)]
+    pub restart_on_image_update: bool,
+    #[serde(default = "default_image_update_interval")]
+    pub image_update_interval_seconds: u64,
+    #[serde(default)]
+    pub discovery_retries: u32,
+    #[serde(default = "default_discovery_retry_delay")]
+    pub discovery_retry_delay_seconds: u64,
+    #[serde(default = "default_ps_retries")]
+    pub ps_retries: u32,
+    #[serde(default = "default_ps_retry_delay")]
+    pub ps_retry_delay_seconds: u64,
+    #[serde(default)]
+    pub active_healthcheck: bool,
+    #[serde(default)]
+    pub verify_health: bool,
+    #[serde(default = "default_health_verify_timeout")]
+    pub health_verify_timeout_seconds: u64,
+    #[serde(default)]
+    pub verify_start_time: bool,
+    #[serde(default)]
+    pub inspect_exit_codes: bool,
+    #[serde(default)]
+    pub capture_logs_on_failure: bool,
+    #[serde(default = "default_log_tail_lines")]
+    pub log_tail_lines: usize,
+    #[serde(default = "default_restart_history_size")]
+    pub restart_history_size: usize,
+    pub podman_url: Option<String>,
+    pub podman_user: Option<String>,
+    pub tls_ca: Option<PathBuf>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub control_listen: Option<String>,
+    #[serde(alias = "metrics_auth_token")]
+    pub control_auth_token: Option<String>,
+    #[serde(default)]
+    pub control_auth_reads: bool,
+    pub status_file: Option<PathBuf>,
+    #[serde(default)]
+    pub container_overrides: HashMap<String, ContainerOverride>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+    #[serde(default = "default_notification_queue_capacity")]
+    pub notification_queue_capacity: usize,
+    #[serde(default = "default_notification_max_attempts")]
+    pub notification_max_attempts: u32,
+    #[serde(default)]
+    pub notification_digest_seconds: u64,
+    pub notification_template: Option<String>,
+    pub on_restart_command: Option<String>,
+    pub on_failure_command: Option<String>,
+    #[serde(default = "default_hook_timeout")]
+    pub hook_timeout_seconds: u64,
+    #[serde(default = "default_custom_restart_timeout")]
+    pub custom_restart_timeout_seconds: u64,
+    #[serde(default = "default_restart_hook_timeout")]
+    pub restart_hook_timeout_seconds: u64,
+    pub log_max_files: Option<usize>,
+    pub log_max_total_mb: Option<u64>,
+    #[serde(default = "default_log_buffer_lines")]
+    pub log_buffer_lines: usize,
+}
+
+/// A monitored compose (or Kubernetes YAML) file. Accepts either a bare path
+/// string or a table, so existing configs keep working:
+///
+/// ```toml
+/// compose_files = ["/srv/app/compose.yml", { path = "/srv/old/compose.yml", enabled = false }]
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(from = "ComposeFileEntry")]
+pub struct ComposeFile {
+    pub path: String,
+    pub enabled: bool,
+    /// Overrides `restart_force_recreate` for this file.
+    pub force_recreate: Option<bool>,
+    /// Overrides `restart_pull_always` for this file.
+    pub pull_always: Option<bool>,
+    /// Directory compose runs in, instead of the file's own.
+    pub working_dir: Option<PathBuf>,
+    /// Replica count per service, kept across restarts with `--scale`.
+    pub scale: BTreeMap<String, u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ComposeFileEntry {
+    Path(String),
+    Table {
+        path: String,
+        #[serde(default = "default_enabled")]
+        enabled: bool,
+        force_recreate: Option<bool>,
+        pull_always: Option<bool>,
+        working_dir: Option<PathBuf>,
+        #[serde(default)]
+        scale: BTreeMap<String, u32>,
+    },
 }
 
+impl From<ComposeFileEntry> for ComposeFile {
+    fn from(entry: ComposeFileEntry) -> Self {
+        match entry {
+            ComposeFileEntry::Path(path) => Self {
+                path,
+                enabled: true,
+                force_recreate: None,
+                pull_always: None,
+                working_dir: None,
+                scale: BTreeMap::new(),
+            },
+            ComposeFileEntry::Table {
+                path,
+                enabled,
+                force_recreate,
+                pull_always,
+                working_dir,
+                scale,
+            } => Self {
+                path,
+                enabled,
+                force_recreate,
+                pull_always,
+                working_dir,
+                scale,
+            },
+        }
+    }
+}
+
+/// Operating posture of the monitor.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Detect down containers and restart them
+    #[default]
+    Manage,
+    /// Detect and report down containers, but never touch them
+    Monitor,
+}
+
+/// What the first check cycle after startup does.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StartupAction {
+    /// Check every container and restart the down ones right away
+    #[default]
+    Recover,
+    /// Only record which containers are running; restarts start with the
+    /// next cycle
+    Observe,
+}
+
+/// How the names of template-named containers are compared against the
+/// names podman reports.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NameMatching {
+    /// Tolerate separator, case, replica number and hash differences
+    #[default]
+    Pattern,
+    /// Require the rendered `name_template` exactly
+    Exact,
+}
+
+/// How containers of compose stacks are brought back.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RestartStrategy {
+    /// Always compose down/up the whole stack
+    #[default]
+    Compose,
+    /// Start (or restart) the affected containers in place while they still
+    /// exist, falling back to compose down/up when any must be recreated
+    Container,
+}
+
+/// Per-container settings keyed by resolved container name.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ContainerOverride {
+    pub start_grace_seconds: Option<u64>,
+    pub stop_timeout_seconds: Option<u64>,
+    pub priority: Option<i32>,
+    /// Replaces the global `quiet_hours`; an empty list disables them.
+    pub quiet_hours: Option<Vec<QuietWindow>>,
+    /// Shell command run instead of the usual restart.
+    pub custom_restart_command: Option<String>,
+    /// Second opinion asked before restarting a container podman reports
+    /// down.
+    pub health_probe: Option<HealthProbe>,
+    /// Shell command run before the container is restarted; the restart is
+    /// abandoned if it fails.
+    pub pre_restart_command: Option<String>,
+    /// Shell command run once the restart command succeeded.
+    pub post_restart_command: Option<String>,
+}
+
+/// The two commands sequenced around a container's restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartHook {
+    Pre,
+    Post,
+}
+
+impl RestartHook {
+    /// `PODMON_PHASE` of the hook.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RestartHook::Pre => "pre",
+            RestartHook::Post => "post",
+        }
+    }
+}
+
+impl fmt::Display for RestartHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestartHook::Pre => f.write_str("Pre-restart hook"),
+            RestartHook::Post => f.write_str("Post-restart hook"),
+        }
+    }
+}
+
+/// An independent check of whether a container is up, written
+/// `{ tcp = "127.0.0.1:8080" }` or `{ exec = ["pg_isready", "-q"] }`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthProbe {
+    /// Up while this `host:port` accepts TCP connections
+    Tcp(String),
+    /// Up while this command exits 0 inside the container (`podman exec`)
+    Exec(Vec<String>),
+}
+
+impl fmt::Display for HealthProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthProbe::Tcp(address) => write!(f, "tcp {}", address),
+            HealthProbe::Exec(command) => write!(f, "exec {}", command.join(" ")),
+        }
+    }
+}
+
+/// A daily time-of-day window, written `"HH:MM-HH:MM"`. The end is exclusive,
+/// and a window whose end is before its start wraps past midnight.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietWindow {
+    /// Minutes after midnight
+    start: u16,
+    end: u16,
+}
+
+impl QuietWindow {
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start || minute_of_day < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for QuietWindow {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let parse = |time: &str| -> Option<u16> {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let (hours, minutes): (u16, u16) = (hours.parse().ok()?, minutes.parse().ok()?);
+            (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+        };
+        value
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse(start)?, parse(end)?)))
+            .map(|(start, end)| Self { start, end })
+            .ok_or_else(|| {
+                format!(
+                    "invalid quiet hours window {:?}, expected \"HH:MM-HH:MM\"",
+                    value
+                )
+            })
+    }
+}
+
+impl From<QuietWindow> for String {
+    fn from(window: QuietWindow) -> Self {
+        format!(
+            "{:02}:{:02}-{:02}:{:02}",
+            window.start / 60,
+            window.start % 60,
+            window.end / 60,
+            window.end % 60
+        )
+    }
+}
+
+/// A fixed offset from UTC, written `"+HH:MM"` or `"-HH:MM"`. Daylight saving
+/// is not followed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct UtcOffsetMinutes(i32);
+
+impl TryFrom<String> for UtcOffsetMinutes {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let invalid = || {
+            format!(
+                "invalid UTC offset {:?}, expected \"+HH:MM\" or \"-HH:MM\"",
+                value
+            )
+        };
+        let (sign, rest) = match value.trim().split_at_checked(1) {
+            Some(("+", rest)) => (1, rest),
+            Some(("-", rest)) => (-1, rest),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = rest.split_once(':').ok_or_else(invalid)?;
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 14 || minutes >= 60 {
+            return Err(invalid());
+        }
+        Ok(Self(sign * (hours * 60 + minutes)))
+    }
+}
+
+impl UtcOffsetMinutes {
+    pub fn minutes(self) -> i32 {
+        self.0
+    }
+}
+
+impl From<UtcOffsetMinutes> for String {
+    fn from(offset: UtcOffsetMinutes) -> Self {
+        let sign = if offset.0 < 0 { '-' } else { '+' };
+        let minutes = offset.0.abs();
+        format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotifierConfig {
+    Log,
+}
+
+const SHELL_METACHARACTERS: &[char] = &[';', '|', '&', '$', '`', '<', '>', '\n'];
+
+const fn default_enabled() -> bool {
+    true
+}
 const fn default_check_interval() -> u64 {
     30
 }
@@ -42,13 +643,420 @@ const fn default_status_interval() -> u64 {
 const fn default_max_failures() -> u32 {
     5
 }
+const fn default_restart_attempts() -> u32 {
+    1
+}
+const fn default_down_confirmations() -> u32 {
+    1
+}
+const fn default_restart_retry_delay() -> u64 {
+    5
+}
+const fn default_discovery_retry_delay() -> u64 {
+    10
+}
+const fn default_ps_retries() -> u32 {
+    2
+}
+const fn default_ps_retry_delay() -> u64 {
+    1
+}
+const fn default_custom_restart_timeout() -> u64 {
+    300
+}
+const fn default_restart_hook_timeout() -> u64 {
+    60
+}
+const fn default_image_update_interval() -> u64 {
+    3600
+}
+fn default_name_template() -> String {
+    "{project}_{service}_{index}".to_string()
+}
+const fn default_notification_queue_capacity() -> usize {
+    100
+}
+const fn default_notification_max_attempts() -> u32 {
+    3
+}
+const fn default_health_verify_timeout() -> u64 {
+    60
+}
+const fn default_log_tail_lines() -> usize {
+    20
+}
+const fn default_restart_history_size() -> usize {
+    10
+}
+const fn default_hook_timeout() -> u64 {
+    30
+}
+const fn default_log_buffer_lines() -> usize {
+    1000
+}
+
+/// Config file syntax, by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    fn of(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("toml") => Ok(Self::Toml),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            Some("json") => Ok(Self::Json),
+            _ => anyhow::bail!(
+                "Unsupported config file extension: {} (expected .toml, .yaml, .yml or .json)",
+                path.display()
+            ),
+        }
+    }
+}
 
 impl Config {
     pub fn from_file(path: &Path) -> Result<Self> {
         let content = fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path.display()))?;
 
-        toml::from_str(&content)
-            .with_context(|| format!("Failed to parse config file: {}", path.display()))
+        let config: Self = match ConfigFormat::of(path)? {
+            ConfigFormat::Toml => toml::from_str(&content).map_err(anyhow::Error::from),
+            ConfigFormat::Yaml => serde_yml::from_str(&content).map_err(anyhow::Error::from),
+            ConfigFormat::Json => serde_json::from_str(&content).map_err(anyhow::Error::from),
+        }
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// A configuration with every setting at its default and no compose
+    /// files.
+    pub fn defaults() -> Result<Self> {
+        toml::from_str("compose_files = []").context("Failed to build the default config")
+    }
+
+    /// Renders the configuration as TOML, with every default filled in.
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).context("Failed to serialize config")
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.check_interval_seconds == 0 || self.status_interval_seconds == 0 {
+            anyhow::bail!("check_interval_seconds and status_interval_seconds must be at least 1");
+        }
+        if let Some(listen) = &self.control_listen {
+            self.control_addr(listen)?;
+        }
+        if self
+            .control_auth_token
+            .as_deref()
+            .is_some_and(|token| token.trim().is_empty())
+        {
+            anyhow::bail!("control_auth_token must not be empty; remove it to disable auth");
+        }
+        if self.restart_on_image_update && self.image_update_interval_seconds == 0 {
+            anyhow::bail!("image_update_interval_seconds must be at least 1");
+        }
+        if let Some(template) = &self.notification_template {
+            validate_template(template)?;
+        }
+        if self
+            .max_load_average
+            .is_some_and(|load| !load.is_finite() || load <= 0.0)
+        {
+            anyhow::bail!(
+                "max_load_average must be greater than 0; remove it to disable the check"
+            );
+        }
+        if self.min_available_memory_mb == Some(0) {
+            anyhow::bail!(
+                "min_available_memory_mb must be at least 1; remove it to disable the check"
+            );
+        }
+        for (name, probe) in self
+            .container_overrides
+            .iter()
+            .filter_map(|(name, o)| Some((name, o.health_probe.as_ref()?)))
+        {
+            match probe {
+                HealthProbe::Tcp(address) if !address.contains(':') => anyhow::bail!(
+                    "health_probe tcp address of {} must be host:port: {}",
+                    name,
+                    address
+                ),
+                HealthProbe::Exec(command) if command.is_empty() => {
+                    anyhow::bail!("health_probe exec command of {} must not be empty", name)
+                }
+                _ => {}
+            }
+        }
+        if self.down_confirmations == 0 {
+            anyhow::bail!("down_confirmations must be at least 1");
+        }
+        if !self.name_template.contains("{service}") {
+            anyhow::bail!(
+                "name_template must contain a {{service}} placeholder: {}",
+                self.name_template
+            );
+        }
+        if let Some(percent) = self.min_running_percent
+            && !(0.0..=100.0).contains(&percent)
+        {
+            anyhow::bail!("min_running_percent must be between 0 and 100: {}", percent);
+        }
+        // Arguments are passed to podman-compose directly, not through a
+        // shell, so quoting or shell syntax would arrive literally
+        if let Some(arg) = self
+            .compose_extra_args
+            .iter()
+            .find(|arg| arg.contains(SHELL_METACHARACTERS))
+        {
+            anyhow::bail!(
+                "compose_extra_args entries are not run through a shell, remove shell syntax from: {}",
+                arg
+            );
+        }
+        if self.log_max_files == Some(0) || self.log_max_total_mb == Some(0) {
+            anyhow::bail!(
+                "log_max_files and log_max_total_mb must be at least 1; remove them to keep all logs"
+            );
+        }
+        if self.log_buffer_lines > MAX_LOG_BUFFER_LINES {
+            anyhow::bail!(
+                "log_buffer_lines must be at most {}: {}",
+                MAX_LOG_BUFFER_LINES,
+                self.log_buffer_lines
+            );
+        }
+        for (key, command) in [
+            ("on_restart_command", &self.on_restart_command),
+            ("on_failure_command", &self.on_failure_command),
+        ] {
+            if command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+                anyhow::bail!("{} must not be empty; remove it to disable the hook", key);
+            }
+        }
+        for (name, o) in &self.container_overrides {
+            for (key, command) in [
+                ("pre_restart_command", &o.pre_restart_command),
+                ("post_restart_command", &o.post_restart_command),
+            ] {
+                if command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+                    anyhow::bail!(
+                        "{} of {} must not be empty; remove it to disable the hook",
+                        key,
+                        name
+                    );
+                }
+            }
+        }
+        if self.restart_hook_timeout_seconds == 0 {
+            anyhow::bail!("restart_hook_timeout_seconds must be at least 1");
+        }
+        self.validate_remote_tls()
+    }
+
+    /// The TLS keys are only handed to the podman CLI (and through it to
+    /// podman-compose) as environment variables; there is no API client of
+    /// our own. They take effect with podman 5.6 or later, which the
+    /// monitor checks for at startup, and compose files are still read and
+    /// run from the local host.
+    fn validate_remote_tls(&self) -> Result<()> {
+        let tls = self.remote_tls();
+        if tls.is_empty() {
+            return Ok(());
+        }
+        if !self
+            .podman_url
+            .as_deref()
+            .is_some_and(|url| url.starts_with("tcp://"))
+        {
+            anyhow::bail!(
+                "tls_ca, tls_cert and tls_key only apply to a tcp:// podman_url (with podman 5.6 or later)"
+            );
+        }
+        if tls.cert.is_some() != tls.key.is_some() {
+            anyhow::bail!("tls_cert and tls_key must be set together");
+        }
+        for (key, path) in [
+            ("tls_ca", &tls.ca),
+            ("tls_cert", &tls.cert),
+            ("tls_key", &tls.key),
+        ] {
+            if let Some(path) = path
+                && !path.is_file()
+            {
+                anyhow::bail!("{} not found: {}", key, path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Connection URL for podman commands: `podman_url` if set, otherwise the
+    /// rootless socket of `podman_user`, otherwise podman's own default.
+    pub fn container_host(&self) -> Result<Option<String>> {
+        if let Some(url) = &self.podman_url {
+            return Ok(Some(url.clone()));
+        }
+        self.podman_user
+            .as_deref()
+            .map(rootless_socket_url)
+            .transpose()
+    }
+
+    /// Client certificates for a `tcp://` `podman_url`.
+    pub fn remote_tls(&self) -> RemoteTls {
+        RemoteTls {
+            ca: self.tls_ca.clone(),
+            cert: self.tls_cert.clone(),
+            key: self.tls_key.clone(),
+        }
+    }
+
+    /// The control server address, which must be an IP literal with a port:
+    /// `127.0.0.1:9090`, `[::1]:9090` or `[::]:9090`.
+    pub fn control_addr(&self, listen: &str) -> Result<SocketAddr> {
+        listen.parse().with_context(|| {
+            format!(
+                "control_listen must be an IPv4 or IPv6 address with a port (e.g. 127.0.0.1:9090 or [::]:9090): {}",
+                listen
+            )
+        })
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            name_template: self.name_template.clone(),
+            merge_overrides: self.merge_override_files,
+            name_patterns: self.name_matching == NameMatching::Pattern,
+            scale: BTreeMap::new(),
+        }
+    }
+
+    /// `parse_options` with the `scale` overrides of one compose file entry.
+    pub fn file_parse_options(&self, compose_file: &ComposeFile) -> ParseOptions {
+        ParseOptions {
+            scale: compose_file.scale.clone(),
+            ..self.parse_options()
+        }
+    }
+
+    /// Restart priority: TOML override, then compose label, then 0.
+    pub fn priority(&self, container_name: &str, label_priority: Option<i32>) -> i32 {
+        self.container_overrides
+            .get(container_name)
+            .and_then(|o| o.priority)
+            .or(label_priority)
+            .unwrap_or(0)
+    }
+
+    /// How `up` recreates the stack of `compose_file`: its own entry's
+    /// settings, falling back to the global ones.
+    pub fn up_options(&self, compose_file: &Path) -> UpOptions {
+        let entry = self
+            .compose_files
+            .iter()
+            .find(|entry| Path::new(&entry.path) == compose_file);
+        UpOptions {
+            force_recreate: entry
+                .and_then(|e| e.force_recreate)
+                .unwrap_or(self.restart_force_recreate),
+            pull_always: entry
+                .and_then(|e| e.pull_always)
+                .unwrap_or(self.restart_pull_always),
+            scale: entry.map(|e| e.scale.clone()).unwrap_or_default(),
+        }
+    }
+
+    /// The `working_dir` configured for `compose_file`, if any.
+    pub fn compose_working_dir(&self, compose_file: &Path) -> Option<&Path> {
+        self.compose_files
+            .iter()
+            .find(|entry| Path::new(&entry.path) == compose_file)
+            .and_then(|entry| entry.working_dir.as_deref())
+    }
+
+    /// Whether `now` falls in the container's quiet hours (its override, else
+    /// the global windows), evaluated at `quiet_hours_utc_offset`.
+    pub fn in_quiet_hours(&self, container_name: &str, now: SystemTime) -> bool {
+        let windows = self
+            .container_overrides
+            .get(container_name)
+            .and_then(|o| o.quiet_hours.as_ref())
+            .unwrap_or(&self.quiet_hours);
+        if windows.is_empty() {
+            return false;
+        }
+
+        let utc_minutes = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs() / 60) as i64;
+        let minute_of_day =
+            (utc_minutes + self.quiet_hours_utc_offset.0 as i64).rem_euclid(24 * 60) as u16;
+        windows.iter().any(|window| window.contains(minute_of_day))
+    }
+
+    /// Seconds a container gets to stop before it is killed; `None` leaves
+    /// podman's default.
+    pub fn stop_timeout(&self, container_name: &str) -> Option<u64> {
+        self.container_overrides
+            .get(container_name)
+            .and_then(|o| o.stop_timeout_seconds)
+            .or(self.stop_timeout_seconds)
+    }
+
+    /// The container's `custom_restart_command`, if it has one.
+    pub fn custom_restart_command(&self, container_name: &str) -> Option<&str> {
+        self.container_overrides
+            .get(container_name)
+            .and_then(|o| o.custom_restart_command.as_deref())
+            .filter(|command| !command.trim().is_empty())
+    }
+
+    /// The container's `pre_restart_command` or `post_restart_command`.
+    pub fn restart_hook(&self, container_name: &str, hook: RestartHook) -> Option<&str> {
+        self.container_overrides
+            .get(container_name)
+            .and_then(|o| match hook {
+                RestartHook::Pre => o.pre_restart_command.as_deref(),
+                RestartHook::Post => o.post_restart_command.as_deref(),
+            })
+    }
+
+    /// Whether down containers are restarted: `mode = "manage"` and
+    /// `enable_restarts`.
+    pub fn restarts_enabled(&self) -> bool {
+        self.mode == Mode::Manage && self.enable_restarts
+    }
+
+    /// Why restarts are off, for log and API messages.
+    pub fn restarts_disabled_reason(&self) -> &'static str {
+        if self.mode == Mode::Monitor {
+            "monitor mode"
+        } else {
+            "enable_restarts = false"
+        }
+    }
+
+    pub fn health_probe(&self, container_name: &str) -> Option<&HealthProbe> {
+        self.container_overrides
+            .get(container_name)
+            .and_then(|o| o.health_probe.as_ref())
+    }
+
+    pub fn start_grace(&self, container_name: &str) -> Duration {
+        let seconds = self
+            .container_overrides
+            .get(container_name)
+            .and_then(|o| o.start_grace_seconds)
+            .unwrap_or(0);
+        Duration::from_secs(seconds)
     }
 }
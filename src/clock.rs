@@ -0,0 +1,80 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+// =============================================================================
+// Time Source
+// =============================================================================
+
+/// Where `MonitorState` and `ContainerState` read the current time for
+/// backoff, grace periods, throttling and quiet hours. The system clock follows real (or
+/// Tokio-paused) time; `MockClock` only moves when told to, so time-based
+/// decisions can be exercised without sleeping.
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for decisions tied to the time of day.
+    fn wall_now(&self) -> SystemTime;
+
+    /// Time since `earlier`, zero if `earlier` is in the future.
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
+
+/// Reads `tokio::time::Instant::now()`.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn wall_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that starts at the moment it is created (or at a given wall-clock
+/// time) and only advances through `advance`.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<(Instant, SystemTime)>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::at(SystemTime::now())
+    }
+
+    /// A clock whose wall-clock time starts at `wall`.
+    pub fn at(wall: SystemTime) -> Self {
+        Self {
+            now: Mutex::new((Instant::now(), wall)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        now.0 += by;
+        now.1 += by;
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+
+    fn wall_now(&self) -> SystemTime {
+        self.now.lock().unwrap_or_else(|e| e.into_inner()).1
+    }
+}
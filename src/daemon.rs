@@ -0,0 +1,154 @@
+use crate::shutdown::SIGTERM;
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+// =============================================================================
+// Daemonization
+// =============================================================================
+
+unsafe extern "C" {
+    fn fork() -> i32;
+    fn setsid() -> i32;
+    fn dup2(oldfd: i32, newfd: i32) -> i32;
+    fn kill(pid: i32, sig: i32) -> i32;
+}
+
+/// Forks into the background and returns in the child only; the parent
+/// prints the child's PID and exits. The child leaves the terminal's session,
+/// reads stdin from /dev/null and appends stdout/stderr to `output` (or
+/// discards them without one), then writes its PID to `pid_file`.
+///
+/// Must run before the Tokio runtime or the log writer thread exist: only
+/// the calling thread survives a fork.
+pub fn detach(output: Option<&Path>, pid_file: &Path) -> Result<()> {
+    let output = output.unwrap_or(Path::new("/dev/null"));
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    let out = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(output)
+        .with_context(|| format!("Failed to open {}", output.display()))?;
+    let null = File::open("/dev/null").context("Failed to open /dev/null")?;
+
+    // SAFETY: single-threaded at this point, so the child inherits a
+    // consistent process image
+    match unsafe { fork() } {
+        -1 => return Err(io::Error::last_os_error()).context("Failed to fork"),
+        0 => {}
+        child => {
+            println!("rusty-podmon detached, PID {}", child);
+            std::process::exit(0);
+        }
+    }
+
+    // SAFETY: plain syscalls on descriptors we own
+    unsafe {
+        if setsid() == -1 {
+            return Err(io::Error::last_os_error()).context("Failed to start a new session");
+        }
+        for (fd, target) in [
+            (null.as_raw_fd(), 0),
+            (out.as_raw_fd(), 1),
+            (out.as_raw_fd(), 2),
+        ] {
+            if dup2(fd, target) == -1 {
+                return Err(io::Error::last_os_error()).context("Failed to redirect stdio");
+            }
+        }
+    }
+
+    fs::write(pid_file, format!("{}\n", std::process::id()))
+        .with_context(|| format!("Failed to write PID file: {}", pid_file.display()))
+}
+
+/// Sends SIGTERM to the process recorded in `pid_file`.
+pub fn stop(pid_file: &Path) -> Result<i32> {
+    let content = fs::read_to_string(pid_file)
+        .with_context(|| format!("Failed to read PID file: {}", pid_file.display()))?;
+    let pid: i32 = content
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid PID in {}: {}", pid_file.display(), content.trim()))?;
+    if pid <= 0 {
+        anyhow::bail!("Invalid PID in {}: {}", pid_file.display(), pid);
+    }
+
+    // SAFETY: kill has no memory-safety preconditions
+    if unsafe { kill(pid, SIGTERM) } == -1 {
+        return Err(io::Error::last_os_error()).with_context(|| {
+            format!(
+                "Failed to signal PID {} (stale PID file {}?)",
+                pid,
+                pid_file.display()
+            )
+        });
+    }
+    Ok(pid)
+}
+
+// =============================================================================
+// Instance Lock
+// =============================================================================
+
+/// An exclusive advisory lock on a file, held until dropped (or the process
+/// exits), so two monitors never manage the same containers. The file holds
+/// the owner's PID for the error message of the instance that loses.
+pub struct InstanceLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    /// Takes the lock without waiting; fails if another process holds it.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open lock file: {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut owner = String::new();
+                let _ = file.read_to_string(&mut owner);
+                let owner = match owner.trim() {
+                    "" => String::new(),
+                    pid => format!(" (PID {})", pid),
+                };
+                anyhow::bail!(
+                    "Another rusty-podmon instance{} holds {}; refusing to manage the same containers twice",
+                    owner,
+                    path.display()
+                );
+            }
+            Err(TryLockError::Error(error)) => {
+                return Err(error).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+
+        let mut lock = Self {
+            file,
+            path: path.to_path_buf(),
+        };
+        lock.write_pid()?;
+        Ok(lock)
+    }
+
+    /// Records the current PID; called again after `detach` forks.
+    pub fn write_pid(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        writeln!(self.file, "{}", std::process::id())
+            .with_context(|| format!("Failed to write lock file: {}", self.path.display()))
+    }
+}
@@ -0,0 +1,133 @@
+use crate::podman::parse_json;
+use crate::runner::Invocation;
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+// =============================================================================
+// Container Event Stream
+// =============================================================================
+
+/// Delay before a `podman events` process that exited is started again.
+const RESPAWN_DELAY: Duration = Duration::from_secs(30);
+
+/// Runs `podman events` in the background and sends the name of every
+/// container that dies, so the monitor can check it right away instead of
+/// at its next poll. Polling carries on regardless: when the stream dies
+/// (podman restarted, API socket gone) nothing is lost but latency, and the
+/// stream is restarted after `RESPAWN_DELAY`. The process is killed when the
+/// `EventStream` is dropped.
+pub struct EventStream {
+    child: Arc<Mutex<Option<Child>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl EventStream {
+    pub fn spawn(invocation: Invocation, tx: mpsc::Sender<String>) -> Self {
+        let child = Arc::new(Mutex::new(None));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread_child = Arc::clone(&child);
+        let thread_stopped = Arc::clone(&stopped);
+        thread::spawn(move || {
+            while !thread_stopped.load(Ordering::Relaxed) && !tx.is_closed() {
+                match stream_once(&invocation, &tx, &thread_child, &thread_stopped) {
+                    Ok(()) => warn!(
+                        "podman events stream ended; relying on polling, retrying in {}s",
+                        RESPAWN_DELAY.as_secs()
+                    ),
+                    Err(e) => warn!(
+                        "podman events stream failed ({}); relying on polling, retrying in {}s",
+                        e,
+                        RESPAWN_DELAY.as_secs()
+                    ),
+                }
+                thread::sleep(RESPAWN_DELAY);
+            }
+        });
+
+        Self { child, stopped }
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(child) = self
+            .child
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+        {
+            let _ = child.kill();
+        }
+    }
+}
+
+/// Runs one `podman events` process until its output ends.
+fn stream_once(
+    invocation: &Invocation,
+    tx: &mpsc::Sender<String>,
+    slot: &Mutex<Option<Child>>,
+    stopped: &AtomicBool,
+) -> std::io::Result<()> {
+    let mut child = invocation
+        .to_command()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take();
+    *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(child);
+    if stopped.load(Ordering::Relaxed) {
+        // Dropped while this process was starting
+        if let Some(mut child) = slot.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        return Ok(());
+    }
+    info!("Watching podman events for container deaths");
+
+    if let Some(stdout) = stdout {
+        for line in BufReader::new(stdout).lines() {
+            let line = line?;
+            let Some(name) = died_container(&line) else {
+                continue;
+            };
+            debug!("podman event: {} died", name);
+            if tx.blocking_send(name).is_err() {
+                break;
+            }
+        }
+    }
+
+    if let Some(mut child) = slot.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    Ok(())
+}
+
+/// Container name of a `died` event line, e.g.
+/// `{"ID":"...","Name":"web","Status":"died","Type":"container",...}`.
+fn died_container(line: &str) -> Option<String> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    let event = parse_json(line).ok()?;
+    let status = event.get("Status").and_then(|s| s.as_str());
+    if status.is_some_and(|status| status != "died") {
+        return None;
+    }
+    event
+        .get("Name")
+        .and_then(|name| name.as_str())
+        .map(String::from)
+}
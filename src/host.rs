@@ -0,0 +1,36 @@
+use anyhow::{Context, Result};
+use std::fs;
+
+// =============================================================================
+// Host Load
+// =============================================================================
+
+/// Host load as reported by `/proc` (Linux only).
+#[derive(Debug, Clone, Copy)]
+pub struct HostLoad {
+    /// 1-minute load average
+    pub load_average: f64,
+    /// `MemAvailable`, in MiB
+    pub available_memory_mb: u64,
+}
+
+pub fn read_host_load() -> Result<HostLoad> {
+    let loadavg = fs::read_to_string("/proc/loadavg").context("Failed to read /proc/loadavg")?;
+    let load_average = loadavg
+        .split_whitespace()
+        .next()
+        .and_then(|field| field.parse().ok())
+        .with_context(|| format!("Unexpected /proc/loadavg: {}", loadavg.trim()))?;
+
+    let meminfo = fs::read_to_string("/proc/meminfo").context("Failed to read /proc/meminfo")?;
+    let available_kb: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))
+        .and_then(|rest| rest.split_whitespace().next()?.parse().ok())
+        .context("No MemAvailable in /proc/meminfo")?;
+
+    Ok(HostLoad {
+        load_average,
+        available_memory_mb: available_kb / 1024,
+    })
+}
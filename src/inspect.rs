@@ -0,0 +1,183 @@
+use crate::cli_config::Config;
+use crate::state::{ContainerDetail, RestartOutcome};
+
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+// =============================================================================
+// Container Inspection (client side)
+// =============================================================================
+
+/// Asks the running monitor's control server about one container and prints
+/// the answer. Returns whether the container is managed.
+pub fn inspect(config: &Config, container: &str, json: bool) -> Result<bool> {
+    let (status, body) = query(config, &format!("/containers/{}", container))?;
+    match status {
+        200 if json => println!("{}", body),
+        200 => {
+            let detail: ContainerDetail =
+                serde_json::from_str(&body).context("Failed to parse monitor response")?;
+            print_detail(&detail);
+        }
+        401 => anyhow::bail!("Monitor rejected the request: {}", body),
+        404 => {
+            println!("{}", body);
+            println!(
+                "Check that its compose file is listed (and enabled) in compose_files and that \
+                 the resolved name matches; `rusty-podmon --validate` checks discovery"
+            );
+            return Ok(false);
+        }
+        _ => anyhow::bail!("Monitor answered {}: {}", status, body),
+    }
+    Ok(true)
+}
+
+/// Asks the running monitor why it would or would not restart a container
+/// and prints its answer. Returns whether the container is managed.
+pub fn explain(config: &Config, container: &str) -> Result<bool> {
+    let (status, body) = query(config, &format!("/explain/{}", container))?;
+    match status {
+        200 => println!("{}", body),
+        401 => anyhow::bail!("Monitor rejected the request: {}", body),
+        404 => {
+            println!("{}", body);
+            return Ok(false);
+        }
+        _ => anyhow::bail!("Monitor answered {}: {}", status, body),
+    }
+    Ok(true)
+}
+
+fn query(config: &Config, path: &str) -> Result<(u16, String)> {
+    let listen = config
+        .control_listen
+        .as_deref()
+        .context("this command needs the control server; set control_listen in the config")?;
+
+    let listen = config.control_addr(listen)?;
+    get(listen, path, config.control_auth_token.as_deref())
+}
+
+fn print_detail(detail: &ContainerDetail) {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    let last_exit = match detail.last_exit_code {
+        Some(code) if detail.oom_killed => format!("{} (OOM)", code),
+        Some(code) => code.to_string(),
+        None => "-".to_string(),
+    };
+    let rows = [
+        ("Container", detail.name.clone()),
+        ("Compose file", detail.compose_file.clone()),
+        (
+            "Image",
+            detail
+                .image
+                .clone()
+                .unwrap_or_else(|| "- (built locally)".to_string()),
+        ),
+        ("Running", yes_no(detail.running).to_string()),
+        (
+            "Down since",
+            detail.down_since.clone().unwrap_or_else(|| "-".to_string()),
+        ),
+        (
+            "Last downtime",
+            detail
+                .last_downtime_seconds
+                .map_or("-".to_string(), |seconds| format!("{}s", seconds)),
+        ),
+        (
+            "Restarts",
+            format!(
+                "{}/{} succeeded",
+                detail.restart_count, detail.restart_attempts
+            ),
+        ),
+        (
+            "Success ratio",
+            detail
+                .success_ratio
+                .map_or("-".to_string(), |ratio| format!("{:.0}%", ratio * 100.0)),
+        ),
+        (
+            "Consecutive failures",
+            detail.consecutive_failures.to_string(),
+        ),
+        ("Give-ups", detail.giveups.to_string()),
+        ("Abandoned", yes_no(detail.abandoned).to_string()),
+        ("Awaiting start", yes_no(detail.awaiting_start).to_string()),
+        (
+            "Backoff remaining",
+            format!("{}s", detail.backoff_remaining_seconds),
+        ),
+        (
+            "Last restart",
+            detail
+                .last_restart
+                .clone()
+                .unwrap_or_else(|| "-".to_string()),
+        ),
+        ("Last exit code", last_exit),
+        (
+            "Last restart reason",
+            detail
+                .last_restart_reason
+                .map_or("-", |reason| reason.as_str())
+                .to_string(),
+        ),
+    ];
+    for (label, value) in rows {
+        println!("{:<22}{}", label, value);
+    }
+
+    if detail.history.is_empty() {
+        return;
+    }
+    println!();
+    println!("Recent restarts (oldest first):");
+    for record in &detail.history {
+        let outcome = match record.outcome {
+            RestartOutcome::Succeeded => "succeeded",
+            RestartOutcome::Failed => "failed",
+        };
+        println!(
+            "  {}  {:<10} {}",
+            record.timestamp,
+            record.reason.map_or("-", |reason| reason.as_str()),
+            outcome
+        );
+    }
+}
+
+/// Minimal HTTP/1.1 GET against the control server, which always closes the
+/// connection after one response.
+fn get(listen: SocketAddr, path: &str, token: Option<&str>) -> Result<(u16, String)> {
+    let mut stream = TcpStream::connect(listen)
+        .with_context(|| format!("Failed to connect to the monitor at {}", listen))?;
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let authorization = token
+        .map(|token| format!("Authorization: Bearer {}\r\n", token))
+        .unwrap_or_default();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+        path, listen, authorization
+    )?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .context("Failed to read monitor response")?;
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .context("Malformed monitor response")?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .context("Malformed monitor status line")?;
+    Ok((status, body.to_string()))
+}
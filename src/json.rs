@@ -0,0 +1,11 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+// =============================================================================
+// JSON Rendering
+// =============================================================================
+
+/// Renders any serializable value as compact JSON.
+pub fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).context("Failed to serialize value")
+}
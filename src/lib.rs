@@ -0,0 +1,44 @@
+//! Podman container monitor: discovers containers from compose (or
+//! Kubernetes YAML) files, watches them, and restarts the stacks of those
+//! that go down.
+//!
+//! The `rusty-podmon` binary is a thin CLI over this crate. Embedders usually
+//! need only the re-exported types:
+//!
+//! ```no_run
+//! use rusty_podmon::{Config, ContainerMonitor};
+//! use std::path::PathBuf;
+//!
+//! # async fn embed() -> anyhow::Result<()> {
+//! let path = PathBuf::from("monitor.toml");
+//! let config = Config::from_file(&path)?;
+//! ContainerMonitor::new(config, path)?.run().await
+//! # }
+//! ```
+
+pub mod cli_config;
+pub mod clock;
+pub mod daemon;
+pub mod events;
+mod host;
+pub mod inspect;
+mod json;
+pub mod logging;
+pub mod metrics;
+pub mod monitor;
+pub mod notify;
+pub mod parse;
+pub mod podman;
+mod restart_queue;
+pub mod runner;
+pub mod server;
+pub mod shutdown;
+pub mod state;
+mod systemd;
+pub mod validate;
+
+pub use cli_config::Config;
+pub use monitor::ContainerMonitor;
+pub use parse::{ComposeParser, ContainerSpec, ParseOptions};
+pub use podman::PodmanClient;
+pub use state::MonitorState;
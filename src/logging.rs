@@ -1,41 +1,448 @@
+use crate::cli_config::UtcOffsetMinutes;
+
 use anyhow::{Context, Result};
+use std::collections::VecDeque;
 use std::fs;
-use std::path::Path;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::format_description::OwnedFormatItem;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+use tracing::{info, warn};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+const LOG_FILE_PREFIX: &str = "rusty-podmon.log";
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Upper bound of `log_buffer_lines`
+pub const MAX_LOG_BUFFER_LINES: usize = 10_000;
+/// Longer lines are cut short in the log buffer, so it stays bounded at
+/// roughly `MAX_LOG_BUFFER_LINES` times this.
+const MAX_BUFFERED_LINE_BYTES: usize = 2048;
+
+// =============================================================================
+// Timestamps
+// =============================================================================
+
+/// How log lines are timestamped (`--log-time-format`): RFC3339, or a
+/// strftime-style pattern such as `"%Y-%m-%d %H:%M:%S"`.
+#[derive(Debug, Clone)]
+pub enum LogTimeFormat {
+    Rfc3339,
+    Custom(OwnedFormatItem),
+}
+
+impl FromStr for LogTimeFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        if value.eq_ignore_ascii_case("rfc3339") {
+            return Ok(Self::Rfc3339);
+        }
+        let description = strftime_to_description(value)?;
+        time::format_description::parse_owned::<2>(&description)
+            .map(Self::Custom)
+            .map_err(|e| format!("invalid log time format {:?}: {}", value, e))
+    }
+}
+
+/// Which clock log timestamps show (`--log-timezone`): UTC, the host's
+/// local offset, or a fixed `"+HH:MM"` offset.
+#[derive(Debug, Clone, Copy)]
+pub enum LogTimezone {
+    Utc,
+    Local,
+    Fixed(UtcOffsetMinutes),
+}
+
+impl FromStr for LogTimezone {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "utc" => Ok(Self::Utc),
+            "local" => Ok(Self::Local),
+            _ => UtcOffsetMinutes::try_from(value.to_string())
+                .map(Self::Fixed)
+                .map_err(|e| format!("{} (or \"utc\" or \"local\")", e)),
+        }
+    }
+}
+
+impl LogTimezone {
+    /// The offset to log at, or `None` if the local offset cannot be
+    /// determined. The local offset is read once, so it must be called
+    /// while the process is still single-threaded and does not follow
+    /// later daylight saving changes.
+    pub fn resolve(self) -> Option<UtcOffset> {
+        match self {
+            Self::Utc => Some(UtcOffset::UTC),
+            Self::Local => UtcOffset::current_local_offset().ok(),
+            Self::Fixed(offset) => UtcOffset::from_whole_seconds(offset.minutes() * 60).ok(),
+        }
+    }
+}
+
+/// Formats log timestamps for both the stdout and file layers.
+#[derive(Debug, Clone)]
+pub struct LogTimer {
+    format: LogTimeFormat,
+    offset: UtcOffset,
+}
+
+impl LogTimer {
+    pub fn new(format: LogTimeFormat, offset: UtcOffset) -> Self {
+        Self { format, offset }
+    }
+}
+
+impl FormatTime for LogTimer {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        let now = OffsetDateTime::now_utc().to_offset(self.offset);
+        let formatted = match &self.format {
+            LogTimeFormat::Rfc3339 => now.format(&Rfc3339),
+            LogTimeFormat::Custom(format) => now.format(format),
+        };
+        write!(w, "{}", formatted.map_err(|_| std::fmt::Error)?)
+    }
+}
+
+/// Translates a strftime pattern into a `time` format description.
+fn strftime_to_description(pattern: &str) -> std::result::Result<String, String> {
+    let mut description = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {}
+            '[' | ']' | '\\' => {
+                description.push('\\');
+                description.push(c);
+                continue;
+            }
+            _ => {
+                description.push(c);
+                continue;
+            }
+        }
+        let mut spec = chars.next().map(String::from).unwrap_or_default();
+        if matches!(spec.as_str(), "3" | "6" | "9" | ":") {
+            spec.extend(chars.next());
+        }
+        let item = match spec.as_str() {
+            "Y" => "[year]",
+            "y" => "[year repr:last_two]",
+            "m" => "[month]",
+            "b" | "h" => "[month repr:short]",
+            "B" => "[month repr:long]",
+            "d" => "[day]",
+            "e" => "[day padding:space]",
+            "j" => "[ordinal]",
+            "a" => "[weekday repr:short]",
+            "A" => "[weekday repr:long]",
+            "H" => "[hour]",
+            "I" => "[hour repr:12]",
+            "p" => "[period]",
+            "M" => "[minute]",
+            "S" => "[second]",
+            "f" | "9f" => "[subsecond digits:9]",
+            "3f" => "[subsecond digits:3]",
+            "6f" => "[subsecond digits:6]",
+            "z" => "[offset_hour sign:mandatory][offset_minute]",
+            ":z" => "[offset_hour sign:mandatory]:[offset_minute]",
+            "s" => "[unix_timestamp]",
+            "F" => "[year]-[month]-[day]",
+            "T" => "[hour]:[minute]:[second]",
+            "%" => "%",
+            _ => {
+                return Err(format!(
+                    "unsupported strftime specifier %{} in {:?}",
+                    spec, pattern
+                ));
+            }
+        };
+        description.push_str(item);
+    }
+    Ok(description)
+}
 
 // =============================================================================
 // Logging Setup
 // =============================================================================
 
-pub fn setup_logging(log_dir: &Path, log_level: &str) -> Result<WorkerGuard> {
-    fs::create_dir_all(log_dir)
-        .with_context(|| format!("Failed to create log directory: {}", log_dir.display()))?;
+/// How the stdout layer is rendered, independently of the log file.
+#[derive(Debug, Clone)]
+pub struct ConsoleOutput {
+    /// Level filter for stdout, e.g. "warn"
+    pub level: String,
+    /// Colored, compact lines; meant for a terminal
+    pub color: bool,
+}
 
-    let file_appender = RollingFileAppender::new(Rotation::DAILY, log_dir, "rusty-podmon.log");
-    let (non_blocking_appender, guard) = tracing_appender::non_blocking(file_appender);
+impl ConsoleOutput {
+    /// Colored when stdout is a terminal, unless `no_color` or the
+    /// `NO_COLOR` environment variable says otherwise.
+    pub fn detect(level: &str, no_color: bool) -> Self {
+        let color = !no_color
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            && std::io::stdout().is_terminal();
+        Self {
+            level: level.to_string(),
+            color,
+        }
+    }
+}
 
-    tracing_subscriber::registry()
-        .with(EnvFilter::new(format!("rusty_podmon={}", log_level)))
-        .with(
-            fmt::Layer::new()
-                .with_writer(std::io::stdout)
+/// The most recent log lines, kept in memory for `GET /logs`. Cloning shares
+/// the buffer. Once full, each new line drops the oldest one.
+#[derive(Debug, Clone)]
+pub struct LogBuffer(Arc<Mutex<RecentLines>>);
+
+#[derive(Debug)]
+struct RecentLines {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    /// A buffer of up to `capacity` lines (at most `MAX_LOG_BUFFER_LINES`);
+    /// 0 keeps nothing.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.min(MAX_LOG_BUFFER_LINES);
+        Self(Arc::new(Mutex::new(RecentLines {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        })))
+    }
+
+    fn lines(&self) -> std::sync::MutexGuard<'_, RecentLines> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.lines().capacity
+    }
+
+    /// Resizes the buffer, dropping the oldest lines if it shrinks.
+    pub fn set_capacity(&self, capacity: usize) {
+        let capacity = capacity.min(MAX_LOG_BUFFER_LINES);
+        let mut recent = self.lines();
+        let excess = recent.lines.len().saturating_sub(capacity);
+        recent.lines.drain(..excess);
+        recent.lines.shrink_to(capacity);
+        recent.capacity = capacity;
+    }
+
+    /// The last `count` lines, oldest first.
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let recent = self.lines();
+        let skip = recent.lines.len().saturating_sub(count);
+        recent.lines.iter().skip(skip).cloned().collect()
+    }
+
+    fn push(&self, line: &str) {
+        let mut recent = self.lines();
+        if recent.capacity == 0 {
+            return;
+        }
+        if recent.lines.len() >= recent.capacity {
+            recent.lines.pop_front();
+        }
+        let line = if line.len() <= MAX_BUFFERED_LINE_BYTES {
+            line.to_string()
+        } else {
+            format!(
+                "{}...",
+                &line[..line.floor_char_boundary(MAX_BUFFERED_LINE_BYTES)]
+            )
+        };
+        recent.lines.push_back(line);
+    }
+}
+
+/// The fmt layer hands over each formatted event in a single write.
+impl io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in String::from_utf8_lossy(buf).lines() {
+            if !line.trim().is_empty() {
+                self.push(line);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogBuffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Logs to stdout, to `buffer` and, when `log_dir` is given, to a daily
+/// rolling file in it. `log_level` filters the file and the buffer,
+/// `console.level` stdout. The returned guard (if any) must be held until
+/// exit to flush the file.
+pub fn setup_logging(
+    log_dir: Option<&Path>,
+    log_level: &str,
+    console: ConsoleOutput,
+    timer: LogTimer,
+    buffer: LogBuffer,
+) -> Result<Option<WorkerGuard>> {
+    let (file_layer, guard) = match log_dir {
+        Some(log_dir) => {
+            fs::create_dir_all(log_dir).with_context(|| {
+                format!("Failed to create log directory: {}", log_dir.display())
+            })?;
+
+            let file_appender = RollingFileAppender::new(Rotation::DAILY, log_dir, LOG_FILE_PREFIX);
+            let (non_blocking_appender, guard) = tracing_appender::non_blocking(file_appender);
+            let layer = fmt::Layer::new()
+                .with_writer(non_blocking_appender)
+                .with_timer(timer.clone())
                 .with_target(false)
                 .with_thread_ids(false)
                 .with_file(false)
-                .with_line_number(false),
-        )
+                .with_line_number(false)
+                .with_ansi(false)
+                .with_filter(level_filter(log_level));
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let buffer_layer = fmt::Layer::new()
+        .with_writer(buffer)
+        .with_timer(timer.clone())
+        .with_target(false)
+        .with_thread_ids(false)
+        .with_file(false)
+        .with_line_number(false)
+        .with_ansi(false)
+        .with_filter(level_filter(log_level));
+
+    let console_layer = fmt::Layer::new()
+        .with_writer(std::io::stdout)
+        .with_timer(timer)
+        .with_target(false)
+        .with_thread_ids(false)
+        .with_file(false)
+        .with_line_number(false)
+        .with_ansi(console.color);
+    // Boxed since the compact and full formats are different types
+    let console_layer = if console.color {
+        console_layer.compact().boxed()
+    } else {
+        console_layer.boxed()
+    };
+
+    tracing_subscriber::registry()
+        .with(console_layer.with_filter(level_filter(&console.level)))
+        .with(file_layer)
+        .with(buffer_layer)
+        .init();
+
+    Ok(guard)
+}
+
+/// Warnings and errors only, to stderr without timestamps, for one-shot
+/// commands whose stdout is their output.
+pub fn setup_stderr_logging() {
+    tracing_subscriber::registry()
         .with(
             fmt::Layer::new()
-                .with_writer(non_blocking_appender)
+                .with_writer(std::io::stderr)
+                .without_time()
                 .with_target(false)
-                .with_thread_ids(false)
-                .with_file(false)
-                .with_line_number(false)
-                .with_ansi(false),
+                .with_ansi(std::io::stderr().is_terminal())
+                .with_filter(level_filter("warn")),
         )
         .init();
+}
 
-    Ok(guard)
+fn level_filter(level: &str) -> EnvFilter {
+    EnvFilter::new(format!("rusty_podmon={}", level))
+}
+
+// =============================================================================
+// Log Pruning
+// =============================================================================
+
+/// Deletes the oldest daily log files in `log_dir` now and then hourly, while
+/// there are more than `max_files` of them or they exceed `max_total_mb`.
+/// `tracing-appender` never removes rotated files itself. Does nothing when
+/// neither limit is set. Must be called from within a Tokio runtime.
+pub fn spawn_log_pruner(log_dir: PathBuf, max_files: Option<usize>, max_total_mb: Option<u64>) {
+    if max_files.is_none() && max_total_mb.is_none() {
+        return;
+    }
+    let max_total_bytes = max_total_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = prune_logs(&log_dir, max_files, max_total_bytes) {
+                warn!(
+                    "Failed to prune log directory {}: {:#}",
+                    log_dir.display(),
+                    e
+                );
+            }
+        }
+    });
+}
+
+fn prune_logs(
+    log_dir: &Path,
+    max_files: Option<usize>,
+    max_total_bytes: Option<u64>,
+) -> Result<()> {
+    let dated_prefix = format!("{}.", LOG_FILE_PREFIX);
+    let mut files: Vec<(String, PathBuf, u64)> = Vec::new();
+    for entry in fs::read_dir(log_dir)
+        .with_context(|| format!("Failed to read log directory: {}", log_dir.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(&dated_prefix) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push((name, entry.path(), metadata.len()));
+        }
+    }
+
+    // Suffixes are YYYY-MM-DD, so name order is date order (oldest first)
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut count = files.len();
+    let mut total: u64 = files.iter().map(|(_, _, len)| len).sum();
+    // The newest file is the one being written to and is always kept
+    for (_, path, len) in files.iter().take(files.len().saturating_sub(1)) {
+        let over_count = max_files.is_some_and(|max| count > max);
+        let over_size = max_total_bytes.is_some_and(|max| total > max);
+        if !over_count && !over_size {
+            break;
+        }
+        fs::remove_file(path)
+            .with_context(|| format!("Failed to remove old log file: {}", path.display()))?;
+        info!("Removed old log file {}", path.display());
+        count -= 1;
+        total -= len;
+    }
+    Ok(())
 }
@@ -1,44 +1,158 @@
-mod cli_config;
-mod state;
-mod logging;
-mod monitor;
-mod parse;
-mod podman;
-
-use crate::cli_config::{Args, Config};
-use crate::logging::setup_logging;
-use crate::monitor::ContainerMonitor;
+use rusty_podmon::cli_config::{Args, Command};
+use rusty_podmon::daemon::InstanceLock;
+use rusty_podmon::logging::{
+    ConsoleOutput, LogBuffer, LogTimer, MAX_LOG_BUFFER_LINES, setup_logging, setup_stderr_logging,
+    spawn_log_pruner,
+};
+use rusty_podmon::validate::Validation;
+use rusty_podmon::{ContainerMonitor, daemon, inspect, shutdown, validate};
 
 use anyhow::Result;
 use clap::Parser;
-use tracing::info;
+use std::fs;
+use time::UtcOffset;
+use tracing::{info, warn};
 
 // =============================================================================
 // Main Application
 // =============================================================================
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let args = Args::parse();
 
+    match &args.command {
+        Some(Command::Inspect { container, json }) => {
+            let config = args.config_source().load()?;
+            if !inspect::inspect(&config, container, *json)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Explain { container }) => {
+            let config = args.config_source().load()?;
+            if !inspect::explain(&config, container)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Stop) => {
+            let pid = daemon::stop(&args.pid_file)?;
+            println!("Sent SIGTERM to rusty-podmon (PID {})", pid);
+            return Ok(());
+        }
+        None => {}
+    }
+
+    if args.print_config {
+        // Printed before logging starts so stdout carries only the TOML
+        let config = args.config_source().load()?;
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+
+    if args.validate {
+        let outcome = match args.config_source().load() {
+            Ok(config) => validate::validate(&config),
+            Err(e) => {
+                println!("error: {:#}", e);
+                Validation::ConfigError
+            }
+        };
+        std::process::exit(outcome.exit_code());
+    }
+
+    if args.list_containers {
+        let config = args.config_source().load()?;
+        // Discovery's own warnings (missing or unparsable compose files) go
+        // to stderr, keeping stdout for the list
+        setup_stderr_logging();
+        let containers = validate::list_containers(&config)?;
+        validate::print_containers(&containers, args.json)?;
+        return Ok(());
+    }
+
+    if args.once_per_file {
+        let config = args.config_source().load()?;
+        if !validate::dump_discovery(&config, args.json)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Taken before detaching so a refusal reaches the terminal; the forked
+    // child inherits the lock
+    let mut lock = InstanceLock::acquire(&args.lock_file())?;
+
+    if args.detach {
+        // Before the runtime starts: only the forking thread survives a fork.
+        // --no-log-file keeps log_dir untouched, so stdout is discarded
+        let output = (!args.no_log_file).then(|| args.log_dir.join("rusty-podmon.out"));
+        if output.is_none() {
+            eprintln!("warning: --no-log-file with --detach discards all log output");
+        }
+        daemon::detach(output.as_deref(), &args.pid_file)?;
+        lock.write_pid()?;
+    }
+
+    // The local offset can only be read safely while single-threaded
+    let log_offset = args.log_timezone.resolve();
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(run(&args, log_offset));
+    if args.detach {
+        let _ = fs::remove_file(&args.pid_file);
+    }
+    drop(lock);
+    result
+}
+
+async fn run(args: &Args, log_offset: Option<UtcOffset>) -> Result<()> {
     // Initialize logging
-    let _guard = setup_logging(&args.log_dir, &args.log_level)?;
+    let log_dir = (!args.no_log_file).then_some(args.log_dir.as_path());
+    let timer = LogTimer::new(
+        args.log_time_format.clone(),
+        log_offset.unwrap_or(UtcOffset::UTC),
+    );
+    // Detached, stdout is rusty-podmon.out next to the log file, which has
+    // every line already: only warnings and errors go there by default
+    let console_level = match &args.console_log_level {
+        Some(level) => level.as_str(),
+        None if args.detach => "warn",
+        None => &args.log_level,
+    };
+    let console = ConsoleOutput::detect(console_level, args.no_color);
+    // Resized to log_buffer_lines once the config is loaded
+    let log_buffer = LogBuffer::new(MAX_LOG_BUFFER_LINES);
+    let _guard = setup_logging(log_dir, &args.log_level, console, timer, log_buffer.clone())?;
+    shutdown::install_handlers()?;
 
     info!("Starting Podman Container Monitor");
+    if log_offset.is_none() {
+        warn!("Could not determine the local UTC offset, logging timestamps in UTC");
+    }
+    let config_source = args.config_source();
     info!(
         "Config: {}, Log dir: {}",
-        args.config.display(),
-        args.log_dir.display()
+        config_source,
+        log_dir.map_or("none (stdout only)".into(), |dir| dir.display().to_string())
     );
 
     // Load configuration and start monitoring
-    let config = Config::from_file(&args.config)?;
+    let config = config_source.load()?;
+    log_buffer.set_capacity(config.log_buffer_lines);
     info!(
         "Monitoring: {} compose files, check interval: {}s",
         config.compose_files.len(),
         config.check_interval_seconds
     );
+    if let Some(log_dir) = log_dir {
+        spawn_log_pruner(
+            log_dir.to_path_buf(),
+            config.log_max_files,
+            config.log_max_total_mb,
+        );
+    }
 
-    let mut monitor = ContainerMonitor::new(config, args.config);
+    let mut monitor = ContainerMonitor::new(config, config_source)?.with_log_buffer(log_buffer);
     monitor.run().await
 }
@@ -0,0 +1,278 @@
+use crate::state::RestartReason;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// =============================================================================
+// Metrics
+// =============================================================================
+
+/// Upper bounds (seconds) of the restart duration buckets. Restarts include
+/// the 10s stabilization wait, so anything below that is never observed.
+const RESTART_DURATION_BUCKETS: &[f64] = &[
+    10.0, 15.0, 20.0, 30.0, 45.0, 60.0, 90.0, 120.0, 180.0, 300.0,
+];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Cumulative counts, one per bucket in `RESTART_DURATION_BUCKETS`
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; RESTART_DURATION_BUCKETS.len()];
+        }
+        for (bucket, bound) in self.buckets.iter_mut().zip(RESTART_DURATION_BUCKETS) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// Per compose file
+    restart_durations: BTreeMap<String, Histogram>,
+    restarts_by_reason: BTreeMap<&'static str, u64>,
+    /// Per container, once it has had a restart attempt
+    success_ratios: BTreeMap<String, f64>,
+    /// Per container, once it has recovered from an outage
+    downtimes: BTreeMap<String, Summary>,
+    /// Image of each managed container that declares one
+    images: BTreeMap<String, String>,
+    missing_compose_files: usize,
+    notifications_dropped: u64,
+    maintenance: bool,
+    host_pressure: bool,
+}
+
+/// Counters and histograms served on `/metrics`. Cloned into the control
+/// server, which renders them on its own thread.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Arc<Mutex<Registry>>);
+
+impl Metrics {
+    fn registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Duration of one stack restart, including verification.
+    pub fn observe_restart_duration(&self, compose_file: &str, duration: Duration) {
+        self.registry()
+            .restart_durations
+            .entry(compose_file.to_string())
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Counts one restart attempt of a container.
+    pub fn count_restart(&self, reason: RestartReason) {
+        *self
+            .registry()
+            .restarts_by_reason
+            .entry(reason.as_str())
+            .or_default() += 1;
+    }
+
+    /// Sets a container's restart success ratio; `None` drops the series,
+    /// e.g. when the container is no longer managed.
+    pub fn set_success_ratio(&self, container: &str, ratio: Option<f64>) {
+        let mut registry = self.registry();
+        match ratio {
+            Some(ratio) => {
+                registry.success_ratios.insert(container.to_string(), ratio);
+            }
+            None => {
+                registry.success_ratios.remove(container);
+            }
+        }
+    }
+
+    /// Length of one outage of a container, from first seen down until
+    /// seen running again.
+    pub fn observe_downtime(&self, container: &str, downtime: Duration) {
+        let mut registry = self.registry();
+        let summary = registry.downtimes.entry(container.to_string()).or_default();
+        summary.count += 1;
+        summary.sum += downtime.as_secs_f64();
+    }
+
+    /// Drops a container's downtime series, e.g. when it is no longer
+    /// managed.
+    pub fn forget_downtime(&self, container: &str) {
+        self.registry().downtimes.remove(container);
+    }
+
+    /// Counts one notification dropped from the full queue. Returns the
+    /// total dropped so far.
+    pub fn count_dropped_notification(&self) -> u64 {
+        let mut registry = self.registry();
+        registry.notifications_dropped += 1;
+        registry.notifications_dropped
+    }
+
+    /// Whether maintenance mode suspends restarts.
+    pub fn set_maintenance(&self, active: bool) {
+        self.registry().maintenance = active;
+    }
+
+    /// Whether host load or memory pressure suspends restarts.
+    pub fn set_host_pressure(&self, active: bool) {
+        self.registry().host_pressure = active;
+    }
+
+    /// Compose files of managed containers that no longer exist.
+    pub fn set_missing_compose_files(&self, count: usize) {
+        self.registry().missing_compose_files = count;
+    }
+
+    /// Sets the image a container runs; `None` drops its series.
+    pub fn set_image(&self, container: &str, image: Option<&str>) {
+        let mut registry = self.registry();
+        match image {
+            Some(image) => {
+                registry
+                    .images
+                    .insert(container.to_string(), image.to_string());
+            }
+            None => {
+                registry.images.remove(container);
+            }
+        }
+    }
+
+    /// Renders everything in the Prometheus/OpenMetrics text format.
+    pub fn render(&self) -> String {
+        let registry = self.registry();
+        let mut out = String::new();
+
+        out.push_str("# HELP podmon_restarts_total Container restart attempts by trigger\n");
+        out.push_str("# TYPE podmon_restarts_total counter\n");
+        for (reason, count) in &registry.restarts_by_reason {
+            let _ = writeln!(
+                out,
+                "podmon_restarts_total{{reason=\"{}\"}} {}",
+                reason, count
+            );
+        }
+
+        out.push_str("# HELP podmon_restart_success_ratio Share of a container's restart attempts that succeeded\n");
+        out.push_str("# TYPE podmon_restart_success_ratio gauge\n");
+        for (container, ratio) in &registry.success_ratios {
+            let _ = writeln!(
+                out,
+                "podmon_restart_success_ratio{{container=\"{}\"}} {}",
+                escape_label(container),
+                ratio
+            );
+        }
+
+        out.push_str("# HELP podmon_container_downtime_seconds Time from a container first seen down until it was seen running again\n");
+        out.push_str("# TYPE podmon_container_downtime_seconds summary\n");
+        for (container, summary) in &registry.downtimes {
+            let label = escape_label(container);
+            let _ = writeln!(
+                out,
+                "podmon_container_downtime_seconds_sum{{container=\"{}\"}} {}",
+                label, summary.sum
+            );
+            let _ = writeln!(
+                out,
+                "podmon_container_downtime_seconds_count{{container=\"{}\"}} {}",
+                label, summary.count
+            );
+        }
+
+        out.push_str("# HELP podmon_notifications_dropped_total Notifications dropped because the delivery queue was full\n");
+        out.push_str("# TYPE podmon_notifications_dropped_total counter\n");
+        let _ = writeln!(
+            out,
+            "podmon_notifications_dropped_total {}",
+            registry.notifications_dropped
+        );
+
+        out.push_str("# HELP podmon_maintenance Whether maintenance mode suspends all restarts\n");
+        out.push_str("# TYPE podmon_maintenance gauge\n");
+        let _ = writeln!(out, "podmon_maintenance {}", u8::from(registry.maintenance));
+
+        out.push_str(
+            "# HELP podmon_host_pressure Whether host load or memory pressure suspends restarts\n",
+        );
+        out.push_str("# TYPE podmon_host_pressure gauge\n");
+        let _ = writeln!(
+            out,
+            "podmon_host_pressure {}",
+            u8::from(registry.host_pressure)
+        );
+
+        out.push_str("# HELP podmon_missing_compose_files Compose files of managed containers that no longer exist\n");
+        out.push_str("# TYPE podmon_missing_compose_files gauge\n");
+        let _ = writeln!(
+            out,
+            "podmon_missing_compose_files {}",
+            registry.missing_compose_files
+        );
+
+        out.push_str("# HELP podmon_container_info Image of each managed container\n");
+        out.push_str("# TYPE podmon_container_info gauge\n");
+        for (container, image) in &registry.images {
+            let _ = writeln!(
+                out,
+                "podmon_container_info{{container=\"{}\",image=\"{}\"}} 1",
+                escape_label(container),
+                escape_label(image)
+            );
+        }
+
+        out.push_str("# HELP podmon_restart_duration_seconds Time taken to restart a compose stack, including verification\n");
+        out.push_str("# TYPE podmon_restart_duration_seconds histogram\n");
+        for (compose_file, histogram) in &registry.restart_durations {
+            let label = escape_label(compose_file);
+            for (bound, count) in RESTART_DURATION_BUCKETS.iter().zip(&histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "podmon_restart_duration_seconds_bucket{{compose_file=\"{}\",le=\"{}\"}} {}",
+                    label, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "podmon_restart_duration_seconds_bucket{{compose_file=\"{}\",le=\"+Inf\"}} {}",
+                label, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "podmon_restart_duration_seconds_sum{{compose_file=\"{}\"}} {}",
+                label, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "podmon_restart_duration_seconds_count{{compose_file=\"{}\"}} {}",
+                label, histogram.count
+            );
+        }
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,12 +1,40 @@
-use crate::cli_config::Config;
-use crate::parse::ComposeParser;
-use crate::podman::PodmanClient;
-use crate::state::{ContainerState, MonitorState};
-
-use anyhow::Result;
-use std::path::PathBuf;
-use std::time::Duration;
-use tokio::time::{interval, sleep};
+use crate::cli_config::{
+    Config, ConfigSource, HealthProbe, RestartHook, RestartStrategy, StartupAction,
+};
+use crate::clock::Clock;
+use crate::events::EventStream;
+use crate::host;
+use crate::json::to_json;
+use crate::logging::LogBuffer;
+use crate::notify::{EventKind, Notifier, RestartEvent, build_notifier};
+use crate::parse::{ComposeParser, ContainerSpec, LABEL_ENABLED, SourceKind};
+use crate::podman::{
+    ComposeTool, ContainerExit, HealthStatus, LabeledContainer, PodmanClient, RemoteTls,
+    find_podman_sockets,
+};
+use crate::restart_queue::{ReadyRestart, RestartJob, RestartQueue};
+use crate::runner::{CommandRunner, SystemRunner};
+use crate::server::{
+    ControlAuth, ControlRequest, ControlResponse, MaintenanceAction, spawn_server,
+};
+use crate::shutdown;
+use crate::state::{ContainerState, MonitorState, RestartOutcome, RestartReason, StatusReport};
+use crate::systemd;
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+use tokio::net::{TcpStream, lookup_host};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::mpsc;
+use tokio::time::{
+    Instant, Interval, MissedTickBehavior, interval, interval_at, sleep, sleep_until, timeout,
+};
 use tracing::{debug, error, info, warn};
 
 // =============================================================================
@@ -15,59 +43,497 @@ use tracing::{debug, error, info, warn};
 
 pub struct ContainerMonitor {
     config: Config,
-    config_path: PathBuf,
+    /// Reread every cycle to pick up changes
+    config_source: ConfigSource,
     state: MonitorState,
+    notifier: Box<dyn Notifier>,
+    podman: PodmanClient,
+    user_mismatch_hinted: bool,
+    fleet_degraded: bool,
+    /// Image ID each container was last recreated for by
+    /// `restart_on_image_update`
+    image_updates: HashMap<String, String>,
+    /// Compose files of managed containers found missing, and since when
+    missing_compose_files: HashMap<PathBuf, Instant>,
+    maintenance: Maintenance,
+    /// Why restarts are suspended by `max_load_average` or
+    /// `min_available_memory_mb`, while they are
+    host_pressure: Option<String>,
+    /// Paces restarts with `restart_spacing_seconds`; restarts run inline
+    /// without it
+    restart_queue: Option<RestartQueue>,
+    /// Recent log lines served on `/logs`
+    log_buffer: LogBuffer,
+}
+
+/// Global suspension of restarts, through `/maintenance` or the
+/// `maintenance_file` sentinel.
+#[derive(Debug, Default)]
+struct Maintenance {
+    /// Switched on through the control API: by whom, and until when
+    api: Option<(String, Option<Instant>)>,
+    /// Whether `maintenance_file` existed at the last check
+    file: bool,
+}
+
+impl Maintenance {
+    fn active(&self) -> bool {
+        self.api.is_some() || self.file
+    }
 }
 
 impl ContainerMonitor {
-    pub fn new(config: Config, config_path: PathBuf) -> Self {
-        Self {
-            config,
-            config_path,
-            state: MonitorState::new(),
+    /// `source` is where `config` came from: usually the config file's path.
+    pub fn new(config: Config, source: impl Into<ConfigSource>) -> Result<Self> {
+        Self::with_runner(config, source, Arc::new(SystemRunner))
+    }
+
+    /// Like `new`, but runs every podman and compose command through
+    /// `runner`, e.g. a `MockRunner` that simulates containers.
+    pub fn with_runner(
+        config: Config,
+        source: impl Into<ConfigSource>,
+        runner: Arc<dyn CommandRunner>,
+    ) -> Result<Self> {
+        let state = MonitorState::new();
+        let notifier = build_notifier(&config, runner.clone(), state.metrics.clone());
+        let container_host = config.container_host()?;
+        let tls = config.remote_tls();
+        if let Some(host) = container_host
+            .as_deref()
+            .filter(|h| h.starts_with("tcp://") && tls.is_empty())
+        {
+            // Without client certificates podman's tcp:// transport is
+            // plaintext and unauthenticated
+            warn!(
+                "podman_url {} is unencrypted; set tls_ca, tls_cert and tls_key or use ssh:// for remote hosts",
+                host
+            );
+        }
+        let compose_tool = config.compose_command.unwrap_or_else(|| {
+            ComposeTool::detect(&runner).unwrap_or_else(|| {
+                warn!("Neither 'podman compose' nor 'podman-compose' responded, assuming podman-compose");
+                ComposeTool::Standalone
+            })
+        });
+        info!(
+            "Using {} for compose stacks{}",
+            compose_tool.as_str(),
+            if config.compose_command.is_some() {
+                " (configured)"
+            } else {
+                " (detected)"
+            }
+        );
+        let podman = PodmanClient::with_runner(container_host, compose_tool, runner).with_tls(tls);
+        if !podman.tls().is_empty() {
+            match podman.version() {
+                Ok(version) if version < RemoteTls::MIN_PODMAN => warn!(
+                    "podman {}.{} ignores tls_ca, tls_cert and tls_key and connects in plaintext; {}.{} or later is required",
+                    version.0,
+                    version.1,
+                    RemoteTls::MIN_PODMAN.0,
+                    RemoteTls::MIN_PODMAN.1
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("Could not check podman's TLS support: {:#}", e),
+            }
         }
+        Ok(Self {
+            config,
+            config_source: source.into(),
+            state,
+            notifier,
+            podman,
+            user_mismatch_hinted: false,
+            fleet_degraded: false,
+            image_updates: HashMap::new(),
+            missing_compose_files: HashMap::new(),
+            maintenance: Maintenance::default(),
+            host_pressure: None,
+            restart_queue: None,
+            log_buffer: LogBuffer::new(0),
+        })
     }
 
-    async fn discover_containers(&mut self) -> Result<()> {
+    /// Serves the lines `buffer` collects on the control server's `/logs`.
+    /// Call before `run`.
+    pub fn with_log_buffer(mut self, buffer: LogBuffer) -> Self {
+        self.log_buffer = buffer;
+        self
+    }
+
+    /// Reads time from `clock` (e.g. a `MockClock`) for backoff, grace
+    /// periods, throttling and scheduling. Call before `run`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        let metrics = self.state.metrics.clone();
+        self.state = MonitorState::with_clock(clock);
+        // Shared with the notifier built in `new`
+        self.state.metrics = metrics;
+        self
+    }
+
+    /// Returns the number of enabled compose files that were missing.
+    async fn discover_containers(&mut self) -> Result<usize> {
         info!(
             "Discovering containers from {} compose files",
             self.config.compose_files.len()
         );
 
-        self.state.clear_managed();
+        let previous: HashSet<String> = self.state.managed_containers.keys().cloned().collect();
+        let labeled = if self.config.label_matching {
+            labeled_containers(&self.podman)
+        } else {
+            Vec::new()
+        };
+        let (specs, missing) = discover(&self.config, &labeled);
 
-        for compose_path_str in &self.config.compose_files {
-            let compose_path = PathBuf::from(compose_path_str);
+        let discovered: HashSet<String> = specs.iter().map(|(spec, _)| spec.name.clone()).collect();
+        for (container_spec, compose_path) in specs {
+            self.state
+                .metrics
+                .set_image(&container_spec.name, container_spec.image.as_deref());
+            self.state.add_container(container_spec, compose_path);
+        }
+        self.forget_containers(&discovered);
+        log_discovery_diff(&previous, &discovered);
 
-            if !compose_path.exists() {
-                warn!("Compose file not found: {}", compose_path_str);
-                continue;
+        info!(
+            "Discovered {} containers total",
+            self.state.managed_containers.len()
+        );
+        Ok(missing)
+    }
+
+    /// Ends an expired API maintenance window and follows the
+    /// `maintenance_file` sentinel, logging every change.
+    fn refresh_maintenance(&mut self) {
+        if let Some((_, Some(until))) = &self.maintenance.api
+            && *until <= self.state.now()
+        {
+            info!("Maintenance mode expired, resuming restarts");
+            self.maintenance.api = None;
+        }
+
+        let file = self
+            .config
+            .maintenance_file
+            .as_deref()
+            .is_some_and(Path::exists);
+        if file != self.maintenance.file {
+            let path = self
+                .config
+                .maintenance_file
+                .as_deref()
+                .unwrap_or(Path::new(""));
+            if file {
+                warn!(
+                    "Maintenance mode on: {} exists, restarts suspended",
+                    path.display()
+                );
+            } else {
+                info!(
+                    "Maintenance file {} removed{}",
+                    path.display(),
+                    if self.maintenance.api.is_some() {
+                        ""
+                    } else {
+                        ", resuming restarts"
+                    }
+                );
+            }
+            self.maintenance.file = file;
+        }
+        self.state
+            .metrics
+            .set_maintenance(self.maintenance.active());
+    }
+
+    /// Plain-text description of maintenance mode, for status and the API.
+    fn maintenance_summary(&self) -> Option<String> {
+        let mut causes = Vec::new();
+        if let Some((initiator, until)) = &self.maintenance.api {
+            causes.push(match until {
+                Some(until) => format!(
+                    "by {}, {} remaining",
+                    initiator,
+                    format_duration(until.saturating_duration_since(self.state.now()))
+                ),
+                None => format!("by {}, until switched off", initiator),
+            });
+        }
+        if self.maintenance.file
+            && let Some(path) = &self.config.maintenance_file
+        {
+            causes.push(format!("while {} exists", path.display()));
+        }
+        (!causes.is_empty()).then(|| format!("restarts suspended ({})", causes.join("; ")))
+    }
+
+    fn handle_maintenance(&mut self, action: MaintenanceAction) -> ControlResponse {
+        self.refresh_maintenance();
+        match action {
+            MaintenanceAction::Status => {}
+            MaintenanceAction::On {
+                duration,
+                initiator,
+            } => {
+                let duration = duration.or_else(|| {
+                    let max = self.config.maintenance_max_seconds;
+                    (max > 0).then(|| Duration::from_secs(max))
+                });
+                let until = duration.map(|duration| self.state.now() + duration);
+                warn!(
+                    "Maintenance mode on, requested by {}: restarts suspended{}",
+                    initiator,
+                    duration.map_or(String::new(), |d| format!(" for {}", format_duration(d)))
+                );
+                self.maintenance.api = Some((initiator, until));
+            }
+            MaintenanceAction::Off { initiator } => {
+                if self.maintenance.api.take().is_some() {
+                    info!("Maintenance mode off, requested by {}", initiator);
+                }
+            }
+        }
+        self.state
+            .metrics
+            .set_maintenance(self.maintenance.active());
+        match self.maintenance_summary() {
+            Some(summary) => ControlResponse::new(200, format!("maintenance: {}", summary)),
+            None => ControlResponse::new(200, "maintenance: off"),
+        }
+    }
+
+    /// Compares host load and available memory against `max_load_average`
+    /// and `min_available_memory_mb`, logging when pressure starts and ends.
+    fn refresh_host_pressure(&mut self) {
+        if self.config.max_load_average.is_none() && self.config.min_available_memory_mb.is_none() {
+            return;
+        }
+        let load = match host::read_host_load() {
+            Ok(load) => load,
+            Err(e) => {
+                debug!("Could not read host load: {:#}", e);
+                return;
+            }
+        };
+
+        let mut causes = Vec::new();
+        if let Some(max) = self.config.max_load_average
+            && load.load_average > max
+        {
+            causes.push(format!("load average {:.2} > {}", load.load_average, max));
+        }
+        if let Some(min) = self.config.min_available_memory_mb
+            && load.available_memory_mb < min
+        {
+            causes.push(format!(
+                "available memory {} MB < {} MB",
+                load.available_memory_mb, min
+            ));
+        }
+        let pressure = (!causes.is_empty()).then(|| causes.join(", "));
+
+        match (&self.host_pressure, &pressure) {
+            (None, Some(cause)) => warn!(
+                "Host under pressure ({}), backing off: restarts suspended",
+                cause
+            ),
+            (Some(_), None) => info!(
+                "Host pressure eased (load average {:.2}, available memory {} MB), resuming restarts",
+                load.load_average, load.available_memory_mb
+            ),
+            _ => {}
+        }
+        self.host_pressure = pressure;
+        self.state
+            .metrics
+            .set_host_pressure(self.host_pressure.is_some());
+    }
+
+    /// Logs each ended outage and records its length.
+    fn record_recoveries(&self, recovered: Vec<(String, Duration)>) {
+        for (name, downtime) in recovered {
+            info!(
+                "Container {} recovered after {} down",
+                name,
+                format_duration(downtime)
+            );
+            self.state.metrics.observe_downtime(&name, downtime);
+        }
+    }
+
+    /// Drops the state of every managed container not in `keep`.
+    fn forget_containers(&mut self, keep: &HashSet<String>) {
+        for (name, state) in self.state.prune_managed(keep) {
+            self.state.metrics.set_success_ratio(&name, None);
+            self.state.metrics.set_image(&name, None);
+            self.state.metrics.forget_downtime(&name);
+            self.image_updates.remove(&name);
+            info!(
+                "No longer managing {} (was in {}), dropping its state (restarts: {}, consecutive failures: {})",
+                name,
+                state.compose_file.display(),
+                state.restart_successes,
+                state.consecutive_failures
+            );
+        }
+    }
+
+    /// Notices compose files deleted while running. Their containers are not
+    /// restarted, as there is nothing to restart them from, until the file
+    /// is back; with `missing_compose_file_prune_seconds` they are dropped
+    /// once it has been gone that long.
+    fn check_compose_files(&mut self) {
+        let files: BTreeSet<PathBuf> = self
+            .state
+            .managed_containers
+            .values()
+            .map(|state| state.compose_file.clone())
+            .collect();
+        let now = self.state.now();
+        for file in &files {
+            if file.exists() {
+                if self.missing_compose_files.remove(file).is_some() {
+                    info!(
+                        "Compose file {} is back, resuming restarts of its containers",
+                        file.display()
+                    );
+                }
+            } else if !self.missing_compose_files.contains_key(file) {
+                warn!(
+                    "Compose file {} disappeared; its containers will not be restarted until it is back",
+                    file.display()
+                );
+                self.missing_compose_files.insert(file.clone(), now);
             }
+        }
+        self.missing_compose_files
+            .retain(|file, _| files.contains(file));
 
-            match ComposeParser::parse_containers(&compose_path) {
-                Ok(containers) => {
-                    debug!(
-                        "Found {} containers in {}",
-                        containers.len(),
-                        compose_path_str
+        let grace = Duration::from_secs(self.config.missing_compose_file_prune_seconds);
+        if !grace.is_zero() {
+            let expired: Vec<PathBuf> = self
+                .missing_compose_files
+                .iter()
+                .filter(|(_, since)| self.state.elapsed(**since) >= grace)
+                .map(|(file, _)| file.clone())
+                .collect();
+            if !expired.is_empty() {
+                for file in &expired {
+                    warn!(
+                        "Compose file {} missing for {}s, no longer managing its containers",
+                        file.display(),
+                        grace.as_secs()
                     );
+                    self.missing_compose_files.remove(file);
+                }
+                let keep: HashSet<String> = self
+                    .state
+                    .managed_containers
+                    .iter()
+                    .filter(|(_, state)| !expired.contains(&state.compose_file))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                self.forget_containers(&keep);
+            }
+        }
+
+        self.state
+            .metrics
+            .set_missing_compose_files(self.missing_compose_files.len());
+    }
 
-                    for container_spec in containers {
-                        self.state
-                            .add_container(container_spec.name, compose_path.clone());
+    /// `podman ps`, retried up to `ps_retries` times so a transient podman
+    /// hiccup doesn't cost a whole check cycle.
+    async fn poll_running(&self) -> Result<HashSet<String>> {
+        let retries = self.config.ps_retries;
+        let delay = Duration::from_secs(self.config.ps_retry_delay_seconds);
+        let mut attempt = 0;
+        loop {
+            match self.podman.get_running_containers() {
+                Ok(running) => return Ok(running),
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    warn!(
+                        "Failed to list running containers, retry {}/{} in {}s: {:#}",
+                        attempt,
+                        retries,
+                        delay.as_secs(),
+                        e
+                    );
+                    tokio::select! {
+                        _ = sleep(delay) => {}
+                        _ = shutdown::wait() => return Err(e),
                     }
                 }
-                Err(e) => {
-                    error!("Failed to parse compose file {}: {:#}", compose_path_str, e);
-                }
+                Err(e) => return Err(e),
             }
         }
+    }
 
-        info!(
-            "Discovered {} containers total",
-            self.state.managed_containers.len()
-        );
-        Ok(())
+    /// Initial discovery, retried while compose files are missing or nothing
+    /// was found, for files on storage that is still being mounted at boot.
+    async fn initial_discovery(&mut self) -> Result<()> {
+        let retries = self.config.discovery_retries;
+        let delay = Duration::from_secs(self.config.discovery_retry_delay_seconds);
+        let mut attempt = 0;
+        loop {
+            let missing = self.discover_containers().await?;
+            let empty = self.state.managed_containers.is_empty();
+            if (missing == 0 && !empty) || attempt >= retries {
+                return Ok(());
+            }
+            attempt += 1;
+            warn!(
+                "Discovery incomplete ({} compose files missing, {} containers found), retry {}/{} in {}s",
+                missing,
+                self.state.managed_containers.len(),
+                attempt,
+                retries,
+                delay.as_secs()
+            );
+            tokio::select! {
+                _ = sleep(delay) => {}
+                _ = shutdown::wait() => return Ok(()),
+            }
+        }
+    }
+
+    /// Replaces a template-generated name with the real container that carries
+    /// matching compose project/service labels, if one exists. Replicas of a
+    /// scaled service take the matching containers in name order.
+    fn match_by_labels(spec: &mut ContainerSpec, labeled: &[LabeledContainer]) {
+        if spec.explicit_name {
+            return;
+        }
+
+        let mut matches: Vec<&str> = labeled
+            .iter()
+            .filter(|container| {
+                container.service.as_deref() == Some(spec.service.as_str())
+                    && container.project.is_some()
+                    && container.project == spec.project
+            })
+            .map(|container| container.name.as_str())
+            .collect();
+        matches.sort_unstable();
+        let matched = matches.get(spec.index as usize - 1).copied();
+
+        if let Some(name) = matched
+            && name != spec.name
+        {
+            debug!(
+                "Matched service {} to container {} via labels (template name: {})",
+                spec.service, name, spec.name
+            );
+            spec.name = name.to_string();
+        }
+        if matched.is_some() {
+            // The labeled name is the real one
+            spec.name_pattern = None;
+        }
     }
 
     fn should_restart_container(
@@ -75,179 +541,2357 @@ impl ContainerMonitor {
         container_name: &str,
         container_state: &ContainerState,
     ) -> bool {
-        if container_state.consecutive_failures >= self.config.max_consecutive_failures {
-            debug!(
-                "Skipping {} - too many failures: {}/{}",
-                container_name,
-                container_state.consecutive_failures,
-                self.config.max_consecutive_failures
-            );
-            return false;
+        match self.restart_blocker(container_name, container_state) {
+            None => true,
+            Some(RestartBlocker::QuietHours) => {
+                info!("Restart of {} suppressed: quiet hours", container_name);
+                false
+            }
+            Some(blocker) => {
+                debug!("Skipping {} - {}", container_name, blocker);
+                false
+            }
+        }
+    }
+
+    /// Why a container that needs a restart would not get one right now.
+    fn restart_blocker(
+        &self,
+        container_name: &str,
+        container_state: &ContainerState,
+    ) -> Option<RestartBlocker> {
+        if self.maintenance.active() {
+            return Some(RestartBlocker::Maintenance);
+        }
+
+        if self.host_pressure.is_some() {
+            return Some(RestartBlocker::HostPressure);
+        }
+
+        if container_state.abandoned {
+            return Some(RestartBlocker::Abandoned {
+                giveups: container_state.giveups,
+            });
+        }
+
+        if let Some(cap) = self.failure_cap(container_state)
+            && container_state.consecutive_failures >= cap
+        {
+            return Some(RestartBlocker::FailureCap {
+                failures: container_state.consecutive_failures,
+                cap,
+            });
+        }
+
+        let required = self.config.down_confirmations;
+        if !self.state.is_running(container_name)
+            && container_state.consecutive_down_checks < required
+        {
+            return Some(RestartBlocker::Unconfirmed {
+                seen: container_state.consecutive_down_checks,
+                required,
+            });
+        }
+
+        if container_state.awaiting_start {
+            return Some(RestartBlocker::AwaitingStart);
+        }
+
+        if self
+            .restart_queue
+            .as_ref()
+            .is_some_and(|queue| queue.contains(container_name))
+        {
+            return Some(RestartBlocker::Queued);
+        }
+
+        if self
+            .config
+            .in_quiet_hours(container_name, self.state.wall_now())
+        {
+            return Some(RestartBlocker::QuietHours);
         }
 
         if container_state.is_in_backoff() {
-            debug!(
-                "Skipping {} - in backoff: {}s remaining",
-                container_name,
-                container_state.backoff_duration().as_secs()
+            return Some(RestartBlocker::Backoff {
+                remaining: container_state.backoff_remaining(),
+            });
+        }
+
+        let min_interval = Duration::from_secs(self.config.min_stack_restart_interval_seconds);
+        if let Some(remaining) = self
+            .state
+            .stack_restart_remaining(&container_state.compose_file, min_interval)
+        {
+            return Some(RestartBlocker::StackThrottled { remaining });
+        }
+
+        if self
+            .missing_compose_files
+            .contains_key(&container_state.compose_file)
+        {
+            return Some(RestartBlocker::ComposeFileMissing);
+        }
+
+        None
+    }
+
+    /// Describes what the monitor would do about a container right now,
+    /// following the same decisions as a check cycle.
+    fn explain(&self, container_name: &str) -> ControlResponse {
+        let Some(state) = self.state.managed_containers.get(container_name) else {
+            return ControlResponse::new(
+                404,
+                format!(
+                    "{} is not managed: it is not defined in any enabled compose file, \
+                     is excluded (restart: \"no\" or a falsy {} label), or its resolved \
+                     name differs",
+                    container_name, LABEL_ENABLED
+                ),
             );
-            return false;
+        };
+
+        let running = self.state.is_running(container_name);
+        let probed = self.config.active_healthcheck && state.healthcheck.is_some();
+        let explanation = if !self.config.restarts_enabled() {
+            format!(
+                "{} is {}; restarts are disabled ({})",
+                container_name,
+                if running { "running" } else { "down" },
+                self.config.restarts_disabled_reason()
+            )
+        } else if running && !probed {
+            format!("{} is running; no restart needed", container_name)
+        } else if running && state.in_healthcheck_start_period() {
+            format!(
+                "{} is running and inside its healthcheck start period; not probed yet",
+                container_name
+            )
+        } else if let Some(blocker) = self.restart_blocker(container_name, state) {
+            format!(
+                "{} is {}, but would not be restarted: {}",
+                container_name,
+                if running { "running" } else { "down" },
+                blocker
+            )
+        } else if running {
+            format!(
+                "{} is running; its healthcheck is probed each check and a failing probe restarts it",
+                container_name
+            )
+        } else {
+            format!(
+                "{} is down and would be restarted at its next check",
+                container_name
+            )
+        };
+        ControlResponse::new(200, explanation)
+    }
+
+    fn failure_cap(&self, state: &ContainerState) -> Option<u32> {
+        state.failure_cap(
+            self.config.max_consecutive_failures,
+            self.config.respect_restart_policy,
+        )
+    }
+
+    fn is_unhealthy(&self, container_name: &str) -> bool {
+        match self.podman.run_healthcheck(container_name) {
+            Ok(true) => false,
+            Ok(false) => {
+                warn!(
+                    "Container {} is running but failed its healthcheck",
+                    container_name
+                );
+                true
+            }
+            Err(e) => {
+                debug!("Could not probe {}: {:#}", container_name, e);
+                false
+            }
         }
+    }
 
-        true
+    /// Asks a container's `health_probe`, if it has one, whether it is up
+    /// after all, e.g. because `podman ps` glitched. A probe that cannot be
+    /// run confirms the container as down.
+    async fn probe_says_up(&self, container_name: &str) -> bool {
+        let Some(probe) = self.config.health_probe(container_name) else {
+            return false;
+        };
+        let up = match probe {
+            HealthProbe::Tcp(address) => tcp_reachable(address).await,
+            HealthProbe::Exec(command) => self
+                .podman
+                .exec_succeeds(container_name, command, PROBE_TIMEOUT)
+                .await
+                .unwrap_or_else(|e| {
+                    debug!("Could not probe {}: {:#}", container_name, e);
+                    false
+                }),
+        };
+        if up {
+            warn!(
+                "podman reports {} down but its health probe ({}) succeeded, not restarting",
+                container_name, probe
+            );
+        } else {
+            debug!(
+                "Health probe ({}) confirms {} is down",
+                probe, container_name
+            );
+        }
+        up
     }
 
-    async fn check_and_restart_containers(&mut self) -> Result<()> {
-        debug!("Checking container states");
+    /// Finds containers that need a restart, grouped by compose file and
+    /// ordered by priority (highest first, ties broken by name).
+    async fn select_restart_candidates(
+        &mut self,
+        due: &HashSet<String>,
+    ) -> Vec<(PathBuf, Vec<String>)> {
+        let mut candidates = Vec::new();
 
-        // Always reload config to check for changes (removed/added compose files)
-        match Config::from_file(&self.config_path) {
-            Ok(new_config) => {
-                if new_config.compose_files != self.config.compose_files {
-                    info!("Configuration changed, rediscovering containers");
-                    self.config = new_config;
-                    self.discover_containers().await?;
-                    return Ok(()); // Skip this check cycle after rediscovery
+        for name in due {
+            let Some(state) = self.state.managed_containers.get(name) else {
+                continue;
+            };
+            let is_down = !self.state.is_running(name);
+            let probe = self.config.active_healthcheck
+                && state.healthcheck.is_some()
+                && !state.in_healthcheck_start_period();
+
+            if !is_down && !probe {
+                continue;
+            }
+            if !self.should_restart_container(name, state) {
+                continue;
+            }
+            if is_down && self.probe_says_up(name).await {
+                continue;
+            }
+            // Only probe once backoff allows a restart, so failing probes
+            // don't spawn a podman process every cycle
+            if !is_down && !self.is_unhealthy(name) {
+                continue;
+            }
+
+            candidates.push((name.clone(), is_down));
+        }
+
+        // One batched inspect for every down candidate
+        let exits = if self.config.inspect_exit_codes {
+            let down: Vec<String> = candidates
+                .iter()
+                .filter(|(_, is_down)| *is_down)
+                .map(|(name, _)| name.clone())
+                .collect();
+            Some(self.inspect_exits(&down))
+        } else {
+            None
+        };
+
+        // Containers with a custom restart command are restarted on their own
+        let mut compose_files_to_restart: HashMap<(PathBuf, Option<String>), Vec<String>> =
+            HashMap::new();
+        for (name, is_down) in candidates {
+            let Some(state) = self.state.managed_containers.get_mut(&name) else {
+                continue;
+            };
+
+            let mut reason = if is_down {
+                RestartReason::Down
+            } else {
+                RestartReason::Unhealthy
+            };
+            if let (true, Some(exits)) = (is_down, &exits) {
+                let exit = exits.get(&name).copied();
+                state.last_exit = exit;
+                match exit {
+                    Some(exit) if exit.oom_killed => reason = RestartReason::OomKilled,
+                    Some(exit) if exit.exit_code != 0 => reason = RestartReason::Crashed,
+                    Some(_) => {
+                        debug!("Skipping {} - exited cleanly with code 0", name);
+                        continue;
+                    }
+                    None => {}
                 }
             }
+            state.last_restart_reason = Some(reason);
+
+            let custom = self
+                .config
+                .custom_restart_command(&name)
+                .map(|_| name.clone());
+            compose_files_to_restart
+                .entry((state.compose_file.clone(), custom))
+                .or_default()
+                .push(name);
+        }
+
+        let priority = |name: &String| {
+            let label_priority = self
+                .state
+                .managed_containers
+                .get(name)
+                .and_then(|state| state.label_priority);
+            self.config.priority(name, label_priority)
+        };
+
+        let mut ordered: Vec<(i32, PathBuf, Vec<String>)> = compose_files_to_restart
+            .into_iter()
+            .map(|((compose_file, _), mut names)| {
+                names.sort_by(|a, b| priority(b).cmp(&priority(a)).then_with(|| a.cmp(b)));
+                let top = names.iter().map(priority).max().unwrap_or(0);
+                (top, compose_file, names)
+            })
+            .collect();
+        ordered.sort_by(|(pa, _, na), (pb, _, nb)| pb.cmp(pa).then_with(|| na.cmp(nb)));
+
+        ordered
+            .into_iter()
+            .map(|(_, compose_file, names)| (compose_file, names))
+            .collect()
+    }
+
+    fn inspect_exits(&self, names: &[String]) -> HashMap<String, ContainerExit> {
+        if names.is_empty() {
+            return HashMap::new();
+        }
+
+        let exits = match self.podman.inspect_many(names) {
+            Ok(exits) => exits,
+            Err(e) => {
+                debug!("Could not inspect {:?}: {:#}", names, e);
+                return HashMap::new();
+            }
+        };
+
+        for name in names {
+            match exits.get(name) {
+                Some(exit) if exit.oom_killed => warn!(
+                    "Container {} was OOM killed (exit code {}), consider raising its memory limit",
+                    name, exit.exit_code
+                ),
+                Some(exit) if exit.exit_code != 0 => warn!(
+                    "Container {} crashed with exit code {}",
+                    name, exit.exit_code
+                ),
+                Some(_) => {}
+                None => debug!("Container {} does not exist yet", name),
+            }
+        }
+
+        exits
+    }
+
+    /// Evaluates the containers whose scheduled check is due, then schedules
+    /// their next one.
+    async fn run_check_cycle(&mut self) -> Result<()> {
+        let now = self.state.now();
+        let due = self.state.schedule.take_due(now);
+        let result = self.check_and_restart_containers(&due).await;
+        for name in &due {
+            self.schedule_next_check(name);
+        }
+        result
+    }
+
+    /// A down container in backoff is next looked at when its backoff ends and
+    /// one awaiting verification after a short delay; everything else after
+    /// a check interval.
+    fn schedule_next_check(&mut self, container_name: &str) {
+        let Some(state) = self.state.managed_containers.get(container_name) else {
+            return;
+        };
+        let check_interval = Duration::from_secs(self.config.check_interval_seconds);
+        let min_stack_interval =
+            Duration::from_secs(self.config.min_stack_restart_interval_seconds);
+        let down = !self.state.is_running(container_name);
+        let delay = if state.awaiting_start {
+            PENDING_RECHECK.min(check_interval)
+        } else if down && state.is_in_backoff() {
+            state.backoff_remaining()
+        } else if down
+            && let Some(remaining) = self
+                .state
+                .stack_restart_remaining(&state.compose_file, min_stack_interval)
+        {
+            remaining
+        } else {
+            check_interval
+        };
+        self.state
+            .schedule
+            .schedule(container_name, self.state.now() + delay);
+    }
+
+    /// Re-reads the config file: a changed compose file list triggers
+    /// rediscovery, and new check and status intervals are picked up by the
+    /// run loop, which rebuilds its timers.
+    async fn reload_config(&mut self) {
+        let new_config = match self.config_source.load() {
+            Ok(new_config) => new_config,
             Err(e) => {
                 warn!("Failed to reload config: {:#}", e);
+                return;
+            }
+        };
+        if new_config.compose_files != self.config.compose_files {
+            info!("Configuration changed, rediscovering containers");
+            self.config = new_config;
+            if let Err(e) = self.discover_containers().await {
+                error!("Rediscovery failed: {:#}", e);
             }
+            return;
         }
+        self.config.check_interval_seconds = new_config.check_interval_seconds;
+        self.config.status_interval_seconds = new_config.status_interval_seconds;
+    }
+
+    async fn check_and_restart_containers(&mut self, due: &HashSet<String>) -> Result<()> {
+        debug!("Checking container states");
 
+        self.refresh_maintenance();
+        self.refresh_host_pressure();
+        self.check_compose_files();
         if self.state.managed_containers.is_empty() {
             debug!("No containers to check");
             return Ok(());
         }
 
         // Update running container state
-        let running = PodmanClient::get_running_containers().map_err(|e| {
+        let running = self.poll_running().await.map_err(|e| {
             error!("Failed to get running containers: {:#}", e);
             e
         })?;
 
-        self.state.update_running(running);
+        if running.is_empty() {
+            self.hint_user_mismatch();
+        }
+        let recovered = self.state.update_running(running, due);
+        self.record_recoveries(recovered);
+        self.check_fleet_threshold().await;
 
-        // Find containers that need restart, grouped by compose file
-        let mut compose_files_to_restart: std::collections::HashMap<PathBuf, Vec<String>> =
-            std::collections::HashMap::new();
-        for (name, state) in &self.state.managed_containers {
-            if !self.state.is_running(name) && self.should_restart_container(name, state) {
-                compose_files_to_restart
-                    .entry(state.compose_file.clone())
-                    .or_default()
-                    .push(name.clone());
-            }
+        if !self.config.restarts_enabled() {
+            self.report_down_transitions().await;
+            return Ok(());
         }
 
+        self.resolve_pending_starts(due).await;
+        self.reset_recovered();
+
+        let compose_files_to_restart = self.select_restart_candidates(due).await;
+
         // Process each container that needs restart
         for (compose_file, container_names) in compose_files_to_restart {
             info!(
-                "Restarting compose file {} containing missing containers: {:?}",
+                "Restarting compose file {} containing failed containers: {:?}",
                 compose_file.display(),
                 container_names
             );
-
-            match PodmanClient::restart_compose_service(&compose_file) {
-                Ok(()) => {
-                    // Wait for container to stabilize
-                    sleep(Duration::from_secs(10)).await;
-
-                    // Verify restart success
-                    if let Ok(running) = PodmanClient::get_running_containers() {
-                        for container_name in container_names {
-                            if running.contains(&container_name) {
-                                info!("Successfully restarted container: {}", container_name);
-                                if let Some(state) =
-                                    self.state.managed_containers.get_mut(&container_name)
-                                {
-                                    state.record_success();
-                                }
-                            } else {
-                                error!(
-                                    "Container {} failed to start after restart",
-                                    container_name
-                                );
-                                if let Some(state) =
-                                    self.state.managed_containers.get_mut(&container_name)
-                                {
-                                    state.record_failure();
-                                }
-                            }
-                        }
-                    }
-                }
-
-                Err(e) => {
-                    error!(
-                        "Failed to restart container {}: {:#}",
-                        compose_file.display(),
-                        e
-                    );
-                    for container_name in container_names {
-                        if let Some(state) = self.state.managed_containers.get_mut(&container_name)
-                        {
-                            state.record_failure();
-                        }
-                    }
-                }
-            }
+            self.restart_or_enqueue(compose_file, container_names).await;
         }
 
         Ok(())
     }
 
-    fn print_status(&self) {
-        let total = self.state.managed_containers.len();
-        let running = self.state.running_managed_count();
-
-        info!("Status: {}/{} managed containers running", running, total);
+    /// Restarts a stack right away, or with `restart_spacing_seconds` queues
+    /// it for `run_queued_restart`.
+    async fn restart_or_enqueue(&mut self, compose_file: PathBuf, container_names: Vec<String>) {
+        let spacing = Duration::from_secs(self.config.restart_spacing_seconds);
+        match &mut self.restart_queue {
+            Some(queue) => queue.push(compose_file, container_names, spacing),
+            None => self.perform_restart(&compose_file, &container_names).await,
+        }
+    }
 
-        // Log containers with restart history
-        for (name, state) in &self.state.managed_containers {
-            if state.restart_count > 0 || state.consecutive_failures > 0 {
-                info!(
-                    "Container {} - restarts: {}, consecutive failures: {}",
-                    name, state.restart_count, state.consecutive_failures
-                );
-            }
+    /// Runs a restart the queue handed back, for the containers that still
+    /// need it: queued restarts are dropped for containers that came back
+    /// on their own or were removed, and under maintenance or host
+    /// pressure.
+    async fn run_queued_restart(&mut self, job: RestartJob) {
+        if let Some(queue) = &mut self.restart_queue {
+            queue.take(&job);
+        }
+        let names: Vec<String> = job
+            .container_names
+            .into_iter()
+            .filter(|name| self.still_needs_restart(name))
+            .collect();
+        if names.is_empty() {
+            info!(
+                "Dropping queued restart of {}: no longer needed",
+                job.compose_file.display()
+            );
+            return;
         }
+        self.perform_restart(&job.compose_file, &names).await;
     }
 
-    async fn startup_recovery(&mut self) -> Result<()> {
-        info!("Performing startup container recovery");
-        self.check_and_restart_containers().await?;
-        info!("Startup recovery completed");
-        Ok(())
+    fn still_needs_restart(&self, container_name: &str) -> bool {
+        let Some(state) = self.state.managed_containers.get(container_name) else {
+            return false;
+        };
+        if self.maintenance.active() || self.host_pressure.is_some() {
+            return false;
+        }
+        // Only these restart containers that are running
+        matches!(
+            state.last_restart_reason,
+            Some(RestartReason::Unhealthy | RestartReason::ImageUpdate)
+        ) || !self.state.is_running(container_name)
     }
 
-    pub async fn run(&mut self) -> Result<()> {
-        // Initial setup
-        self.discover_containers().await?;
-        self.startup_recovery().await?;
+    /// Restarts one stack, recording how long it took. Failures are logged
+    /// and recorded per container by `restart_stack`.
+    async fn perform_restart(&mut self, compose_file: &Path, container_names: &[String]) {
+        let started = Instant::now();
+        let _ = self.restart_stack(compose_file, container_names).await;
+        self.state
+            .metrics
+            .observe_restart_duration(&compose_file.display().to_string(), started.elapsed());
+    }
 
-        // Set up monitoring intervals
-        let mut check_interval = interval(Duration::from_secs(self.config.check_interval_seconds));
-        let mut status_interval =
-            interval(Duration::from_secs(self.config.status_interval_seconds));
+    /// Restarts one compose stack and verifies the given containers came back.
+    /// Returns an error if the restart command failed or any container did not
+    /// start (containers still within their start grace are not failures).
+    async fn restart_stack(
+        &mut self,
+        compose_file: &Path,
+        container_names: &[String],
+    ) -> Result<()> {
+        self.state.record_stack_restart(compose_file);
+        for container_name in container_names {
+            if let Some(state) = self.state.managed_containers.get_mut(container_name) {
+                state.record_attempt();
+                let reason = state.last_restart_reason.unwrap_or(RestartReason::Down);
+                self.state.metrics.count_restart(reason);
+            }
+        }
 
-        info!(
-            "Entering monitoring loop (check: {}s, status: {}s)",
-            self.config.check_interval_seconds, self.config.status_interval_seconds
-        );
+        let source = container_names
+            .first()
+            .and_then(|name| self.state.managed_containers.get(name))
+            .map_or(SourceKind::Compose, |state| state.source);
+        let pod = if self.config.pod_restarts && source == SourceKind::Compose {
+            self.shared_pod(container_names)
+        } else {
+            None
+        };
+        // A newer image only takes effect in a recreated container
+        let recreate = container_names.iter().any(|name| {
+            self.state
+                .managed_containers
+                .get(name)
+                .is_some_and(|state| state.last_restart_reason == Some(RestartReason::ImageUpdate))
+        });
+        let custom = match container_names {
+            [name] => self
+                .config
+                .custom_restart_command(name)
+                .map(|command| (name.as_str(), command)),
+            _ => None,
+        };
+        let target = match (pod.as_deref(), custom) {
+            _ if recreate => RestartTarget::Stack(source),
+            (_, Some((container, command))) => RestartTarget::Custom { container, command },
+            (Some(pod), None) => RestartTarget::Pod(pod),
+            (None, None)
+                if source == SourceKind::Compose
+                    && self.config.restart_strategy == RestartStrategy::Container
+                    && self.all_exist(container_names) =>
+            {
+                RestartTarget::Containers(container_names)
+            }
+            (None, None) => RestartTarget::Stack(source),
+        };
 
-        // Main monitoring loop
-        loop {
-            tokio::select! {
-                _ = check_interval.tick() => {
-                    if let Err(e) = self.check_and_restart_containers().await {
-                        error!("Container check cycle failed: {:#}", e);
-                    }
-                }
-                _ = status_interval.tick() => {
-                    self.print_status();
-                }
+        let restarted_stack = matches!(target, RestartTarget::Pod(_) | RestartTarget::Stack(_));
+        // A stack or pod restart takes down its running containers as well
+        let hooked: Vec<String> = if restarted_stack {
+            let mut names: Vec<String> = self
+                .state
+                .managed_containers
+                .iter()
+                .filter(|(_, state)| state.compose_file == compose_file)
+                .map(|(name, _)| name.clone())
+                .collect();
+            names.sort();
+            names
+        } else {
+            container_names.to_vec()
+        };
+        let start_times = self.start_times(container_names);
+        let result = match self.run_pre_restart_hooks(compose_file, &hooked).await {
+            Ok(()) => self.run_restart_attempts(compose_file, target).await,
+            Err(e) => Err(e.context("Restart abandoned")),
+        };
+        if let Err(e) = result {
+            error!(
+                "Failed to restart container {}: {:#}",
+                compose_file.display(),
+                e
+            );
+            for container_name in container_names {
+                self.record_restart_failure(container_name).await;
             }
+            return Err(e);
         }
+
+        // Wait for container to stabilize
+        sleep(Duration::from_secs(10)).await;
+        for name in &hooked {
+            self.run_restart_hook(RestartHook::Post, compose_file, name)
+                .await;
+        }
+
+        // Verify restart success
+        let running = self.poll_running().await?;
+        // A stack or pod restart also brings back the file's other down
+        // containers, not only the ones that triggered it
+        let siblings_down: Vec<String> = if restarted_stack {
+            self.state
+                .managed_containers
+                .iter()
+                .filter(|(name, state)| {
+                    state.compose_file == compose_file
+                        && !container_names.contains(name)
+                        && !self.state.is_running(name)
+                })
+                .map(|(name, _)| name.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let outages = self.state.observe_running(running);
+        self.record_recoveries(outages);
+        let mut recovered: Vec<String> = siblings_down
+            .into_iter()
+            .filter(|name| self.state.is_running(name))
+            .collect();
+        recovered.sort();
+        let mut failed = Vec::new();
+        for container_name in container_names {
+            match self.verify_start(container_name, self.state.is_running(container_name)) {
+                Verification::Started if !self.started_since(container_name, &start_times) => {
+                    warn!(
+                        "Container {} is running but its start time did not change; it \
+                         recovered on its own before the restart",
+                        container_name
+                    );
+                    if let Some(state) = self.state.managed_containers.get_mut(container_name) {
+                        state.awaiting_start = false;
+                        state.reset_backoff();
+                    }
+                }
+                Verification::Started => {
+                    info!("Successfully restarted container: {}", container_name);
+                    self.record_restart_success(container_name).await;
+                }
+                Verification::Pending { reason, window } => {
+                    info!(
+                        "Container {} {}, deferring verification for up to {}s",
+                        container_name,
+                        reason,
+                        window.as_secs()
+                    );
+                    if let Some(state) = self.state.managed_containers.get_mut(container_name) {
+                        state.awaiting_start = true;
+                    }
+                }
+                Verification::Failed { reason } => {
+                    error!("Container {} {}", container_name, reason);
+                    self.log_container_tail(container_name);
+                    self.record_restart_failure(container_name).await;
+                    failed.push(container_name.as_str());
+                }
+            }
+        }
+
+        for container_name in &recovered {
+            info!(
+                "Container {} came back with the restart of {}",
+                container_name,
+                compose_file.display()
+            );
+            if let Some(state) = self.state.managed_containers.get_mut(container_name) {
+                // The stack restart was an attempt for it too
+                state.restart_attempts += 1;
+                state.last_restart_reason = Some(RestartReason::Down);
+            }
+            self.record_restart_success(container_name).await;
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Containers failed to start: {}",
+                failed.join(", ")
+            ))
+        }
+    }
+
+    /// Runs the `pre_restart_command` of each container in turn. On the
+    /// first failure the post hooks of the containers already prepared run
+    /// to undo them, and the error is returned.
+    async fn run_pre_restart_hooks(
+        &self,
+        compose_file: &Path,
+        containers: &[String],
+    ) -> Result<()> {
+        for (prepared, name) in containers.iter().enumerate() {
+            if self
+                .run_restart_hook(RestartHook::Pre, compose_file, name)
+                .await
+            {
+                continue;
+            }
+            for name in &containers[..prepared] {
+                self.run_restart_hook(RestartHook::Post, compose_file, name)
+                    .await;
+            }
+            anyhow::bail!("{} of {} failed", RestartHook::Pre, name);
+        }
+        Ok(())
+    }
+
+    /// Runs a container's pre- or post-restart hook, if it has one, and
+    /// logs the outcome. Returns whether it succeeded.
+    async fn run_restart_hook(
+        &self,
+        hook: RestartHook,
+        compose_file: &Path,
+        container: &str,
+    ) -> bool {
+        let Some(command) = self.config.restart_hook(container, hook) else {
+            return true;
+        };
+        info!(
+            "Running {} for {}",
+            hook.to_string().to_lowercase(),
+            container
+        );
+        match self
+            .podman
+            .run_restart_hook(
+                hook,
+                command,
+                container,
+                compose_file,
+                Duration::from_secs(self.config.restart_hook_timeout_seconds),
+            )
+            .await
+        {
+            Ok(()) => {
+                info!("{} for {} succeeded", hook, container);
+                true
+            }
+            Err(e) => {
+                error!("{:#}", e);
+                false
+            }
+        }
+    }
+
+    /// Podman only shows containers owned by the calling user, so a monitor
+    /// running as root sees nothing of a rootless user's containers (and vice
+    /// versa). Logged once, since an empty result may also be a real outage.
+    fn hint_user_mismatch(&mut self) {
+        if self.user_mismatch_hinted {
+            return;
+        }
+        self.user_mismatch_hinted = true;
+
+        let sockets = find_podman_sockets();
+        warn!(
+            "podman reports no running containers while {} are managed. If the containers \
+             belong to another user, the monitor may be running as the wrong user \
+             (rootless vs rootful); set podman_user or podman_url to target the right \
+             podman service. Podman sockets found on this host: {:?}",
+            self.state.managed_containers.len(),
+            sockets
+        );
+    }
+
+    /// The pod all of the given containers currently belong to, if any. Looked
+    /// up at restart time since compose may recreate pods.
+    fn shared_pod(&self, container_names: &[String]) -> Option<String> {
+        let containers = match self.podman.get_containers_with_labels() {
+            Ok(containers) => containers,
+            Err(e) => {
+                warn!("Failed to look up pods, using compose restart: {:#}", e);
+                return None;
+            }
+        };
+
+        let mut pods = container_names.iter().map(|name| {
+            containers
+                .iter()
+                .find(|container| &container.name == name)
+                .and_then(|container| container.pod.clone())
+        });
+        let pod = pods.next()??;
+        pods.all(|other| other.as_ref() == Some(&pod))
+            .then_some(pod)
+    }
+
+    /// Whether every named container still exists (stopped or not), so it
+    /// can be started in place rather than recreated.
+    fn all_exist(&self, container_names: &[String]) -> bool {
+        match self.podman.inspect_many(container_names) {
+            Ok(existing) => container_names
+                .iter()
+                .all(|name| existing.contains_key(name)),
+            Err(e) => {
+                debug!(
+                    "Could not inspect {:?}, using compose: {:#}",
+                    container_names, e
+                );
+                false
+            }
+        }
+    }
+
+    /// `down` stops the whole stack at once, so it waits as long as the
+    /// slowest-stopping container of the file needs.
+    fn stack_stop_timeout(&self, compose_file: &Path) -> Option<u64> {
+        self.state
+            .managed_containers
+            .iter()
+            .filter(|(_, state)| state.compose_file == compose_file)
+            .filter_map(|(name, _)| self.config.stop_timeout(name))
+            .max()
+    }
+
+    /// Runs the restart command up to `restart_attempts` times, so transient
+    /// failures (registry hiccups, lock contention) don't count as a failure.
+    async fn run_restart_attempts(
+        &self,
+        compose_file: &Path,
+        target: RestartTarget<'_>,
+    ) -> Result<()> {
+        match target {
+            RestartTarget::Pod(pod) => info!(
+                "Containers of {} share pod {}, restarting the pod",
+                compose_file.display(),
+                pod
+            ),
+            RestartTarget::Containers(names) => info!(
+                "Containers of {} still exist, restarting them in place: {:?}",
+                compose_file.display(),
+                names
+            ),
+            RestartTarget::Custom { container, .. } => {
+                info!("Restarting {} with its custom restart command", container)
+            }
+            RestartTarget::Stack(_) => {}
+        }
+        let attempts = self.config.restart_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            debug!(
+                "Restart attempt {}/{} for {}",
+                attempt,
+                attempts,
+                compose_file.display()
+            );
+            let result = match target {
+                RestartTarget::Pod(pod) => self.podman.restart_pod(pod),
+                RestartTarget::Containers(names) => names.iter().try_for_each(|name| {
+                    self.podman.restart_container(
+                        name,
+                        self.state.is_running(name),
+                        self.config.stop_timeout(name),
+                    )
+                }),
+                RestartTarget::Stack(SourceKind::Compose) => self.podman.restart_compose_service(
+                    compose_file,
+                    self.config.compose_working_dir(compose_file),
+                    &self.config.compose_extra_args,
+                    self.stack_stop_timeout(compose_file),
+                    self.config.up_options(compose_file),
+                ),
+                RestartTarget::Stack(SourceKind::Kube) => self.podman.replay_kube(compose_file),
+                RestartTarget::Custom { container, command } => {
+                    self.podman
+                        .run_custom_restart(
+                            command,
+                            container,
+                            compose_file,
+                            Duration::from_secs(self.config.custom_restart_timeout_seconds),
+                        )
+                        .await
+                }
+            };
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= attempts => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Restart attempt {}/{} for {} failed, retrying in {}s: {:#}",
+                        attempt,
+                        attempts,
+                        compose_file.display(),
+                        self.config.restart_retry_delay_seconds,
+                        e
+                    );
+                    sleep(Duration::from_secs(self.config.restart_retry_delay_seconds)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// In `monitor` mode, reports each container once when it goes down and
+    /// once when it comes back, without restarting anything.
+    async fn report_down_transitions(&mut self) {
+        let mut transitions: Vec<(String, EventKind)> = self
+            .state
+            .managed_containers
+            .iter()
+            .filter_map(
+                |(name, state)| match (self.state.is_running(name), state.reported_down) {
+                    (false, false) => Some((name.clone(), EventKind::ContainerDown)),
+                    (true, true) => Some((name.clone(), EventKind::ContainerRecovered)),
+                    _ => None,
+                },
+            )
+            .collect();
+        transitions.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, kind) in transitions {
+            if let Some(state) = self.state.managed_containers.get_mut(&name) {
+                state.reported_down = kind == EventKind::ContainerDown;
+            }
+            match kind {
+                EventKind::ContainerDown => {
+                    warn!(
+                        "Container {} is down ({}, not restarting)",
+                        name,
+                        self.config.restarts_disabled_reason()
+                    )
+                }
+                _ => info!("Container {} is running again", name),
+            }
+            self.notify(&name, kind).await;
+        }
+    }
+
+    /// Settles containers whose post-restart verification was deferred because
+    /// of a per-container start grace.
+    async fn resolve_pending_starts(&mut self, due: &HashSet<String>) {
+        let pending: Vec<String> = self
+            .state
+            .managed_containers
+            .iter()
+            .filter(|(name, state)| state.awaiting_start && due.contains(*name))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for container_name in pending {
+            let running = self.state.is_running(&container_name);
+            match self.verify_start(&container_name, running) {
+                Verification::Started => {
+                    info!(
+                        "Container {} came up within its verification window",
+                        container_name
+                    );
+                    self.record_restart_success(&container_name).await;
+                }
+                Verification::Pending { .. } => {}
+                Verification::Failed { reason } => {
+                    error!("Container {} {}", container_name, reason);
+                    self.log_container_tail(&container_name);
+                    self.record_restart_failure(&container_name).await;
+                }
+            }
+        }
+    }
+
+    /// Start times of the given containers before a restart, with
+    /// `verify_start_time`. Containers that do not exist (or cannot be
+    /// inspected) are left out.
+    fn start_times(&self, container_names: &[String]) -> HashMap<String, OffsetDateTime> {
+        if !self.config.verify_start_time {
+            return HashMap::new();
+        }
+        container_names
+            .iter()
+            .filter_map(|name| match self.podman.get_start_time(name) {
+                Ok(started) => Some((name.clone(), started)),
+                Err(e) => {
+                    debug!("Could not read start time of {}: {:#}", name, e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Whether a container was started again after its start time in
+    /// `before` was taken. True without an earlier start time to compare, or
+    /// if the current one cannot be read.
+    fn started_since(
+        &self,
+        container_name: &str,
+        before: &HashMap<String, OffsetDateTime>,
+    ) -> bool {
+        let Some(previous) = before.get(container_name) else {
+            return true;
+        };
+        match self.podman.get_start_time(container_name) {
+            Ok(started) => started > *previous,
+            Err(e) => {
+                debug!("Could not read start time of {}: {:#}", container_name, e);
+                true
+            }
+        }
+    }
+
+    /// Decides whether a restarted container counts as started: it must be
+    /// running and, with `verify_health`, report healthy if it defines a
+    /// healthcheck. Inconclusive results stay pending while the container's
+    /// start grace (or health timeout) lasts.
+    fn verify_start(&self, container_name: &str, running: bool) -> Verification {
+        let Some(state) = self.state.managed_containers.get(container_name) else {
+            return Verification::Started;
+        };
+
+        if !running {
+            let grace = self.config.start_grace(container_name);
+            return if state.in_start_grace(grace) {
+                Verification::Pending {
+                    reason: "not up yet",
+                    window: grace,
+                }
+            } else if grace.is_zero() {
+                Verification::Failed {
+                    reason: "failed to start after restart".to_string(),
+                }
+            } else {
+                Verification::Failed {
+                    reason: format!("did not start within its {}s start grace", grace.as_secs()),
+                }
+            };
+        }
+
+        if !self.config.verify_health || state.healthcheck.is_none() {
+            return Verification::Started;
+        }
+
+        let window = Duration::from_secs(self.config.health_verify_timeout_seconds);
+        match self.podman.health_status(container_name) {
+            Ok(HealthStatus::Healthy | HealthStatus::None) => Verification::Started,
+            Ok(HealthStatus::Unhealthy) => Verification::Failed {
+                reason: "is running but unhealthy after restart".to_string(),
+            },
+            Ok(HealthStatus::Starting) if state.in_start_grace(window) => Verification::Pending {
+                reason: "not healthy yet",
+                window,
+            },
+            Ok(HealthStatus::Starting) => Verification::Failed {
+                reason: format!("did not become healthy within {}s", window.as_secs()),
+            },
+            Err(e) => {
+                debug!(
+                    "Could not read health of {}, counting it as started: {:#}",
+                    container_name, e
+                );
+                Verification::Started
+            }
+        }
+    }
+
+    /// Logs the last lines of a container's output, which usually explain why
+    /// it would not start.
+    fn log_container_tail(&self, container_name: &str) {
+        if !self.config.capture_logs_on_failure {
+            return;
+        }
+
+        match self
+            .podman
+            .get_logs(container_name, self.config.log_tail_lines)
+        {
+            Ok(logs) if logs.trim().is_empty() => {
+                warn!("Container {} produced no log output", container_name)
+            }
+            Ok(logs) => warn!(
+                "Last {} log lines of {}:\n{}",
+                self.config.log_tail_lines,
+                container_name,
+                logs.trim_end()
+            ),
+            Err(e) => debug!("Could not read logs of {}: {:#}", container_name, e),
+        }
+    }
+
+    /// A container that exhausted its failure budget but is running again
+    /// (e.g. started by hand) gets a fresh budget.
+    fn reset_recovered(&mut self) {
+        let recovered = self.state.reset_recovered(
+            self.config.max_consecutive_failures,
+            self.config.respect_restart_policy,
+        );
+        for name in recovered {
+            info!(
+                "Container {} is running again, resetting its failure budget",
+                name
+            );
+        }
+    }
+
+    async fn record_restart_success(&mut self, container_name: &str) {
+        if let Some(state) = self.state.managed_containers.get_mut(container_name) {
+            state.record_success();
+            state.record_history(RestartOutcome::Succeeded, self.config.restart_history_size);
+            let ratio = state.success_ratio();
+            self.state.total_restarts += 1;
+            self.state.metrics.set_success_ratio(container_name, ratio);
+            self.notify(container_name, EventKind::RestartSucceeded)
+                .await;
+        }
+    }
+
+    async fn record_restart_failure(&mut self, container_name: &str) {
+        let Some(state) = self.state.managed_containers.get_mut(container_name) else {
+            return;
+        };
+        state.record_failure();
+        state.record_history(RestartOutcome::Failed, self.config.restart_history_size);
+        let ratio = state.success_ratio();
+        self.state.total_failures += 1;
+        self.state.metrics.set_success_ratio(container_name, ratio);
+        let consecutive_failures = state.consecutive_failures;
+        let gave_up = state.is_exhausted(
+            self.config.max_consecutive_failures,
+            self.config.respect_restart_policy,
+        );
+
+        self.notify(container_name, EventKind::RestartFailed).await;
+        if gave_up {
+            warn!(
+                "Giving up on {} after {} consecutive failures",
+                container_name, consecutive_failures
+            );
+            self.notify(container_name, EventKind::GaveUp).await;
+            self.record_giveup(container_name);
+        }
+    }
+
+    fn record_giveup(&mut self, container_name: &str) {
+        let max_giveups = self.config.max_giveups;
+        let Some(state) = self.state.managed_containers.get_mut(container_name) else {
+            return;
+        };
+        if state.record_giveup(max_giveups) {
+            error!(
+                "Abandoning {}: gave up {} times, no longer managed until the config is reloaded or a manual restart is requested",
+                container_name, state.giveups
+            );
+        }
+    }
+
+    async fn notify(&self, container_name: &str, kind: EventKind) {
+        let Some(state) = self.state.managed_containers.get(container_name) else {
+            return;
+        };
+
+        let reason = match kind {
+            EventKind::RestartSucceeded | EventKind::RestartFailed | EventKind::GaveUp => {
+                state.last_restart_reason
+            }
+            _ => None,
+        };
+        let event = RestartEvent {
+            container: container_name.to_string(),
+            compose_file: state.compose_file.clone(),
+            kind,
+            reason,
+            restart_count: state.restart_successes,
+            consecutive_failures: state.consecutive_failures,
+            max_failures: self.failure_cap(state).unwrap_or(0),
+            running: self.state.running_managed_count(),
+            managed: self.state.managed_containers.len(),
+            timestamp: SystemTime::now(),
+            summary: String::new(),
+        };
+        self.deliver(&event).await;
+    }
+
+    async fn notify_fleet(&self, kind: EventKind) {
+        let event = RestartEvent {
+            container: String::new(),
+            compose_file: PathBuf::new(),
+            kind,
+            reason: None,
+            restart_count: 0,
+            consecutive_failures: 0,
+            max_failures: 0,
+            running: self.state.running_managed_count(),
+            managed: self.state.managed_containers.len(),
+            timestamp: SystemTime::now(),
+            summary: String::new(),
+        };
+        self.deliver(&event).await;
+    }
+
+    async fn deliver(&self, event: &RestartEvent) {
+        if let Err(e) = self.notifier.notify(event).await {
+            warn!(
+                "Failed to deliver {} notification for {}: {:#}",
+                event.kind.as_str(),
+                if event.container.is_empty() {
+                    "fleet"
+                } else {
+                    &event.container
+                },
+                e
+            );
+        }
+    }
+
+    /// Alerts once when the share of running managed containers drops below
+    /// `min_running_percent`, and once more when it recovers.
+    async fn check_fleet_threshold(&mut self) {
+        let Some(threshold) = self.config.min_running_percent else {
+            return;
+        };
+        let managed = self.state.managed_containers.len();
+        if managed == 0 {
+            return;
+        }
+
+        let running = self.state.running_managed_count();
+        let percent = running as f64 * 100.0 / managed as f64;
+        let degraded = percent < threshold;
+        if degraded == self.fleet_degraded {
+            return;
+        }
+        self.fleet_degraded = degraded;
+
+        if degraded {
+            error!(
+                "CRITICAL: only {:.1}% of managed containers running ({}/{}), below min_running_percent {}%",
+                percent, running, managed, threshold
+            );
+            self.notify_fleet(EventKind::FleetDegraded).await;
+        } else {
+            info!(
+                "{:.1}% of managed containers running ({}/{}), back above min_running_percent {}%",
+                percent, running, managed, threshold
+            );
+            self.notify_fleet(EventKind::FleetRecovered).await;
+        }
+    }
+
+    fn print_status(&self) {
+        let total = self.state.managed_containers.len();
+        let running = self.state.running_managed_count();
+
+        info!("Status: {}/{} managed containers running", running, total);
+        if let Some(summary) = self.maintenance_summary() {
+            warn!("Maintenance mode: {}", summary);
+        }
+        if let Some(cause) = &self.host_pressure {
+            warn!("Host under pressure ({}): restarts suspended", cause);
+        }
+
+        let down: Vec<&str> = self
+            .state
+            .sorted_containers()
+            .into_iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| !self.state.is_running(name))
+            .collect();
+        if !down.is_empty() {
+            // Cap the list so a mass outage stays one readable line
+            let listed = down.len().min(STATUS_DOWN_LIST_MAX);
+            let more = match down.len() - listed {
+                0 => String::new(),
+                rest => format!(" and {} more", rest),
+            };
+            warn!("DOWN: {}{}", down[..listed].join(", "), more);
+        }
+
+        // Log containers with restart history, grouped by compose file
+        let mut current_file = None;
+        for (name, state) in self.state.sorted_containers() {
+            let has_history = state.abandoned || state.restart_attempts > 0;
+            if has_history && current_file != Some(&state.compose_file) {
+                info!("{}:", state.compose_file.display());
+                current_file = Some(&state.compose_file);
+            }
+
+            if state.abandoned {
+                warn!(
+                    "Container {} - abandoned after {} give-ups",
+                    name, state.giveups
+                );
+            } else if state.restart_attempts > 0 {
+                let last_exit = match state.last_exit {
+                    Some(exit) if exit.oom_killed => {
+                        format!(", last exit: {} (OOM)", exit.exit_code)
+                    }
+                    Some(exit) => format!(", last exit: {}", exit.exit_code),
+                    None => String::new(),
+                };
+                let ratio = state
+                    .success_ratio()
+                    .map(|ratio| format!(" ({:.0}%)", ratio * 100.0))
+                    .unwrap_or_default();
+                info!(
+                    "Container {} - restarts: {}/{} succeeded{}, consecutive failures: {}{}",
+                    name,
+                    state.restart_successes,
+                    state.restart_attempts,
+                    ratio,
+                    state.consecutive_failures,
+                    last_exit
+                );
+            }
+        }
+    }
+
+    fn export_status(&self) {
+        let Some(status_file) = &self.config.status_file else {
+            return;
+        };
+
+        let mut report = self.state.report();
+        report.maintenance = self.maintenance.active();
+        if let Err(e) = write_status_file(status_file, &report) {
+            warn!(
+                "Failed to write status file {}: {:#}",
+                status_file.display(),
+                e
+            );
+        }
+    }
+
+    async fn startup_recovery(&mut self) -> Result<()> {
+        if !self.config.startup_recovery || self.config.startup_action == StartupAction::Observe {
+            return self.startup_observation();
+        }
+        info!("Performing startup container recovery");
+        self.run_check_cycle().await?;
+        info!("Startup recovery completed");
+        Ok(())
+    }
+
+    /// `startup_action = "observe"` or `startup_recovery = false`: records
+    /// which containers are running without restarting anything. Nothing
+    /// counts as a down check yet; the first regular cycle, one check
+    /// interval later, acts as usual.
+    fn startup_observation(&mut self) -> Result<()> {
+        info!("Observing container state at startup, no restarts this cycle");
+        let due = self.state.schedule.take_due(self.state.now());
+        let observed = self
+            .podman
+            .get_running_containers()
+            .map(|running| self.state.observe_running(running));
+        for name in &due {
+            self.schedule_next_check(name);
+        }
+        self.record_recoveries(observed?);
+        info!(
+            "Startup observation: {}/{} managed containers running, restarts begin in {}s",
+            self.state.running_managed_count(),
+            self.state.managed_containers.len(),
+            self.config.check_interval_seconds
+        );
+        Ok(())
+    }
+
+    /// `restart_on_image_update`: pulls the image of each running container
+    /// that could be restarted right now and recreates the stacks of those
+    /// still running an older image. A pulled image is acted on once per
+    /// container, so one that never gets picked up (e.g. the compose file
+    /// pins another tag) doesn't recreate the stack every time; a new image
+    /// that fails to start is handled like any down container, with backoff.
+    async fn check_image_updates(&mut self) {
+        let mut pulled: HashMap<String, Option<String>> = HashMap::new();
+        let mut outdated: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+        let mut updates = Vec::new();
+        for (name, state) in self.state.sorted_containers() {
+            let Some(image) = &state.image else {
+                continue;
+            };
+            if !self.state.is_running(name) || self.restart_blocker(name, state).is_some() {
+                continue;
+            }
+            let latest = pulled.entry(image.clone()).or_insert_with(|| {
+                self.podman
+                    .pull_image(image)
+                    .map_err(|e| warn!("Image update check of {} failed: {:#}", image, e))
+                    .ok()
+            });
+            let Some(latest) = latest else {
+                continue;
+            };
+            let current = match self.podman.container_image_id(name) {
+                Ok(current) => current,
+                Err(e) => {
+                    debug!("Skipping image update check of {}: {:#}", name, e);
+                    continue;
+                }
+            };
+            if current == *latest || self.image_updates.get(name) == Some(latest) {
+                continue;
+            }
+            info!(
+                "Newer image for {} ({}): {} -> {}",
+                name,
+                image,
+                short_id(&current),
+                short_id(latest)
+            );
+            outdated
+                .entry(state.compose_file.clone())
+                .or_default()
+                .push(name.clone());
+            updates.push((name.clone(), latest.clone()));
+        }
+
+        if !self.config.restarts_enabled() {
+            return;
+        }
+        self.image_updates.extend(updates);
+        for (compose_file, names) in outdated {
+            for name in &names {
+                if let Some(state) = self.state.managed_containers.get_mut(name) {
+                    state.last_restart_reason = Some(RestartReason::ImageUpdate);
+                }
+            }
+            info!(
+                "Recreating {} for a newer image: {:?}",
+                compose_file.display(),
+                names
+            );
+            self.restart_or_enqueue(compose_file, names).await;
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        // Initial setup
+        self.initial_discovery().await?;
+        let mut control_rx = self.start_control_server()?;
+        let (_events, mut events_rx) = self.start_event_stream();
+        let mut restart_rx = self.start_restart_queue();
+
+        if !self.config.restarts_enabled() {
+            info!(
+                "Restarts disabled ({}): down containers are reported, never restarted",
+                self.config.restarts_disabled_reason()
+            );
+        }
+
+        if self.config.startup_grace_seconds > 0 {
+            info!(
+                "In startup grace period, waiting {}s before first restart cycle",
+                self.config.startup_grace_seconds
+            );
+            tokio::select! {
+                _ = sleep(Duration::from_secs(self.config.startup_grace_seconds)) => {}
+                _ = shutdown::wait() => {
+                    info!("Shutdown signal received during startup grace, stopping monitor");
+                    systemd::notify_stopping();
+                    self.print_summary();
+                    return Ok(());
+                }
+            }
+        }
+
+        self.startup_recovery().await?;
+        self.record_successful_check();
+        systemd::notify_ready();
+        systemd::check_watchdog_interval(Duration::from_secs(self.config.check_interval_seconds));
+
+        // Containers are evaluated when their scheduled check comes due, and
+        // running state is polled at least once per check interval. The
+        // config file is re-read once per check interval, or on SIGHUP
+        let mut check_seconds = self.config.check_interval_seconds;
+        let mut next_cycle = Instant::now() + Duration::from_secs(check_seconds);
+        let mut next_reload = next_cycle;
+        let mut hangup =
+            signal(SignalKind::hangup()).context("Failed to install the SIGHUP handler")?;
+        // Also drives status_file, which is written even without the log line
+        let mut status_interval = (self.config.enable_status_reports
+            || self.config.status_file.is_some())
+        .then(|| interval(Duration::from_secs(self.config.status_interval_seconds)));
+        let mut image_interval = self.config.restart_on_image_update.then(|| {
+            let period = Duration::from_secs(self.config.image_update_interval_seconds);
+            let mut timer = interval_at(Instant::now() + period, period);
+            // Pulls that overran the period are not made up for
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            timer
+        });
+
+        info!(
+            "Entering monitoring loop (check: {}s, status: {})",
+            self.config.check_interval_seconds,
+            if self.config.enable_status_reports {
+                format!("{}s", self.config.status_interval_seconds)
+            } else {
+                "off".to_string()
+            }
+        );
+
+        // Main monitoring loop
+        loop {
+            // Waiting out the batch window lets one `podman ps` serve every
+            // container that comes due (or dies) close together
+            let wake = self
+                .state
+                .schedule
+                .next_due()
+                .map_or(next_cycle, |due| (due + CHECK_BATCH_WINDOW).min(next_cycle));
+            tokio::select! {
+                _ = sleep_until(wake) => {
+                    match self.run_check_cycle().await {
+                        Ok(()) => self.record_successful_check(),
+                        Err(e) => error!("Container check cycle failed: {:#}", e),
+                    }
+                    next_cycle = Instant::now() + Duration::from_secs(check_seconds);
+                }
+                reload = async {
+                    tokio::select! {
+                        _ = sleep_until(next_reload) => false,
+                        _ = hangup.recv() => true,
+                    }
+                } => {
+                    if reload {
+                        info!("SIGHUP received, reloading config");
+                    }
+                    self.reload_config().await;
+                    if self.config.check_interval_seconds != check_seconds {
+                        check_seconds = self.config.check_interval_seconds;
+                        info!("Check interval changed to {}s", check_seconds);
+                        systemd::check_watchdog_interval(Duration::from_secs(check_seconds));
+                        next_cycle = Instant::now() + Duration::from_secs(check_seconds);
+                    }
+                    if let Some(timer) = &mut status_interval {
+                        reschedule(timer, self.config.status_interval_seconds, "Status");
+                    }
+                    next_reload = Instant::now() + Duration::from_secs(check_seconds);
+                }
+                Some(()) = tick_optional(&mut status_interval) => {
+                    if self.config.enable_status_reports {
+                        self.print_status();
+                    }
+                    self.export_status();
+                }
+                Some(request) = recv_optional(&mut control_rx) => {
+                    self.handle_control(request).await;
+                }
+                Some(name) = recv_optional(&mut events_rx) => {
+                    self.expedite_check(&name);
+                }
+                Some(ReadyRestart { job, done }) = recv_unbounded(&mut restart_rx) => {
+                    self.run_queued_restart(job).await;
+                    let _ = done.send(());
+                }
+                Some(()) = tick_optional(&mut image_interval) => {
+                    self.check_image_updates().await;
+                }
+                _ = shutdown::wait() => {
+                    info!("Shutdown signal received, stopping monitor");
+                    systemd::notify_stopping();
+                    break;
+                }
+            }
+        }
+
+        self.print_summary();
+        Ok(())
+    }
+
+    fn record_successful_check(&self) {
+        let max_age =
+            Duration::from_secs(self.config.check_interval_seconds * HEALTHZ_MISSED_CHECKS);
+        self.state.last_successful_check.beat(max_age);
+        systemd::notify_watchdog();
+    }
+
+    fn print_summary(&self) {
+        let config = &self.config;
+        let mut exhausted: Vec<&str> = self
+            .state
+            .managed_containers
+            .iter()
+            .filter(|(_, state)| {
+                state.is_exhausted(
+                    config.max_consecutive_failures,
+                    config.respect_restart_policy,
+                )
+            })
+            .map(|(name, _)| name.as_str())
+            .collect();
+        exhausted.sort_unstable();
+
+        info!("==================== Session Summary ====================");
+        info!(
+            "Uptime: {}",
+            format_duration(self.state.elapsed(self.state.start_time))
+        );
+        info!("Restarts performed: {}", self.state.total_restarts);
+        info!("Failed restarts: {}", self.state.total_failures);
+        if exhausted.is_empty() {
+            info!("Containers that exhausted their failure budget: none");
+        } else {
+            info!(
+                "Containers that exhausted their failure budget: {}",
+                exhausted.join(", ")
+            );
+        }
+        info!("=========================================================");
+    }
+
+    fn start_control_server(&self) -> Result<Option<mpsc::Receiver<ControlRequest>>> {
+        let Some(listen) = &self.config.control_listen else {
+            return Ok(None);
+        };
+
+        let listen = self.config.control_addr(listen)?;
+        let auth = ControlAuth {
+            token: self.config.control_auth_token.clone(),
+            protect_reads: self.config.control_auth_reads,
+        };
+        if auth.token.is_none() && !listen.ip().is_loopback() {
+            warn!(
+                "Control server on {} accepts restarts from anyone who can reach it; set control_auth_token",
+                listen
+            );
+        }
+
+        let (tx, rx) = mpsc::channel(16);
+        spawn_server(
+            listen,
+            tx,
+            self.state.last_successful_check.clone(),
+            self.state.metrics.clone(),
+            self.log_buffer.clone(),
+            auth,
+        )?;
+        Ok(Some(rx))
+    }
+
+    /// With `watch_events`, streams container deaths from `podman events`.
+    /// The stream is stopped when the returned handle is dropped.
+    fn start_event_stream(&self) -> (Option<EventStream>, Option<mpsc::Receiver<String>>) {
+        if !self.config.watch_events {
+            return (None, None);
+        }
+        let (tx, rx) = mpsc::channel(64);
+        let stream = EventStream::spawn(self.podman.die_events(), tx);
+        (Some(stream), Some(rx))
+    }
+
+    /// With `restart_spacing_seconds`, starts the worker that paces restarts
+    /// and returns where it hands them back.
+    fn start_restart_queue(&mut self) -> Option<mpsc::UnboundedReceiver<ReadyRestart>> {
+        if self.config.restart_spacing_seconds == 0 {
+            return None;
+        }
+        let (queue, ready) = RestartQueue::spawn();
+        self.restart_queue = Some(queue);
+        Some(ready)
+    }
+
+    /// Moves a managed container that just died to the front of the
+    /// schedule, so it is checked now rather than at its next poll.
+    fn expedite_check(&mut self, container_name: &str) {
+        if !self.state.managed_containers.contains_key(container_name) {
+            return;
+        }
+        debug!("{} died, checking it now", container_name);
+        let now = self.state.now();
+        self.state.schedule.schedule(container_name, now);
+    }
+
+    async fn handle_control(&mut self, request: ControlRequest) {
+        match request {
+            ControlRequest::RestartNow {
+                container,
+                initiator,
+                reply,
+            } => {
+                let response = self.restart_now(&container, &initiator).await;
+                let _ = reply.send(response);
+            }
+            ControlRequest::Inspect { container, reply } => {
+                let _ = reply.send(self.inspect(&container));
+            }
+            ControlRequest::Explain { container, reply } => {
+                let _ = reply.send(self.explain(&container));
+            }
+            ControlRequest::Maintenance { action, reply } => {
+                let _ = reply.send(self.handle_maintenance(action));
+            }
+        }
+    }
+
+    fn inspect(&self, container_name: &str) -> ControlResponse {
+        let Some(detail) = self.state.detail(container_name) else {
+            return ControlResponse::new(
+                404,
+                format!("Container {} is not managed", container_name),
+            );
+        };
+        match to_json(&detail) {
+            Ok(json) => ControlResponse::new(200, json),
+            Err(e) => ControlResponse::new(500, format!("{:#}", e)),
+        }
+    }
+
+    /// Forces a restart of the container's stack, bypassing backoff. The
+    /// operator has taken over, so accumulated failures are cleared first.
+    async fn restart_now(&mut self, container_name: &str, initiator: &str) -> ControlResponse {
+        let Some(state) = self.state.managed_containers.get_mut(container_name) else {
+            return ControlResponse::new(
+                404,
+                format!("Container {} is not managed", container_name),
+            );
+        };
+
+        if !self.config.restarts_enabled() {
+            return ControlResponse::new(
+                409,
+                format!(
+                    "Not restarting {}: restarts are disabled ({})",
+                    container_name,
+                    self.config.restarts_disabled_reason()
+                ),
+            );
+        }
+
+        info!(
+            "Manual restart of {} requested by {}",
+            container_name, initiator
+        );
+        state.reset_backoff();
+        state.reenable();
+        state.last_restart_reason = Some(RestartReason::Manual);
+        let compose_file = state.compose_file.clone();
+
+        match self
+            .restart_stack(&compose_file, &[container_name.to_string()])
+            .await
+        {
+            Ok(()) => ControlResponse::new(
+                200,
+                format!(
+                    "Restarted {} via {}",
+                    container_name,
+                    compose_file.display()
+                ),
+            ),
+            Err(e) => ControlResponse::new(
+                500,
+                format!("Restart of {} failed: {:#}", container_name, e),
+            ),
+        }
+    }
+}
+
+/// Parses every enabled compose file of `config`, returning each container
+/// it defines with that file, and the number of enabled files that were
+/// missing. Template-generated names are replaced by the real ones in
+/// `labeled`. A name defined by two files stays with the first.
+pub fn discover(
+    config: &Config,
+    labeled: &[LabeledContainer],
+) -> (Vec<(ContainerSpec, PathBuf)>, usize) {
+    // Resolved name -> the compose file that defined it first
+    let mut owners: HashMap<String, PathBuf> = HashMap::new();
+    let mut specs = Vec::new();
+    let mut missing = 0;
+
+    for compose_file in &config.compose_files {
+        let compose_path_str = &compose_file.path;
+        if !compose_file.enabled {
+            info!("Compose file disabled, skipping: {}", compose_path_str);
+            continue;
+        }
+        let compose_path = PathBuf::from(compose_path_str);
+
+        if !compose_path.exists() {
+            warn!("Compose file not found: {}", compose_path_str);
+            missing += 1;
+            continue;
+        }
+
+        let parse_options = config.file_parse_options(compose_file);
+        match ComposeParser::parse_containers(&compose_path, &parse_options) {
+            Ok(containers) => {
+                debug!(
+                    "Found {} containers in {}",
+                    containers.len(),
+                    compose_path_str
+                );
+
+                for mut container_spec in containers {
+                    ContainerMonitor::match_by_labels(&mut container_spec, labeled);
+                    // Keep the first definition so restarts never target
+                    // whichever file happened to be parsed last
+                    if let Some(owner) = owners.get(&container_spec.name) {
+                        error!(
+                            "Container name collision: {} is defined in both {} and {}; keeping {}",
+                            container_spec.name,
+                            owner.display(),
+                            compose_path.display(),
+                            owner.display()
+                        );
+                        continue;
+                    }
+                    owners.insert(container_spec.name.clone(), compose_path.clone());
+                    specs.push((container_spec, compose_path.clone()));
+                }
+            }
+            Err(e) => {
+                error!("Failed to parse compose file {}: {:#}", compose_path_str, e);
+            }
+        }
+    }
+
+    (specs, missing)
+}
+
+/// Containers carrying compose labels, for `label_matching`. Empty (with a
+/// warning) if podman cannot list them.
+pub fn labeled_containers(podman: &PodmanClient) -> Vec<LabeledContainer> {
+    podman
+        .get_containers_with_labels()
+        .map_err(|e| warn!("Label matching unavailable, using name templates: {:#}", e))
+        .unwrap_or_default()
+}
+
+/// Logs which containers a discovery added to and removed from the managed
+/// set, also as `added`/`removed` fields for structured log consumers.
+fn log_discovery_diff(previous: &HashSet<String>, discovered: &HashSet<String>) {
+    let mut added: Vec<&str> = discovered
+        .difference(previous)
+        .map(String::as_str)
+        .collect();
+    let mut removed: Vec<&str> = previous
+        .difference(discovered)
+        .map(String::as_str)
+        .collect();
+    if added.is_empty() && removed.is_empty() {
+        debug!("Managed containers unchanged by discovery");
+        return;
+    }
+    added.sort_unstable();
+    removed.sort_unstable();
+    info!(
+        added = ?added,
+        removed = ?removed,
+        "Managed containers changed: {} added, {} removed",
+        added.len(),
+        removed.len()
+    );
+}
+
+/// Reason a restart is held back.
+enum RestartBlocker {
+    Abandoned { giveups: u32 },
+    FailureCap { failures: u32, cap: u32 },
+    Unconfirmed { seen: u32, required: u32 },
+    AwaitingStart,
+    Queued,
+    QuietHours,
+    Backoff { remaining: Duration },
+    StackThrottled { remaining: Duration },
+    ComposeFileMissing,
+    Maintenance,
+    HostPressure,
+}
+
+impl fmt::Display for RestartBlocker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestartBlocker::Abandoned { giveups } => {
+                write!(f, "abandoned after {} give-ups", giveups)
+            }
+            RestartBlocker::FailureCap { failures, cap } => {
+                write!(f, "too many failures: {}/{}", failures, cap)
+            }
+            RestartBlocker::Unconfirmed { seen, required } => {
+                write!(f, "down for {}/{} checks", seen, required)
+            }
+            RestartBlocker::AwaitingStart => write!(f, "still within start grace after restart"),
+            RestartBlocker::Queued => {
+                write!(f, "already queued for a restart (restart_spacing_seconds)")
+            }
+            RestartBlocker::QuietHours => write!(f, "quiet hours"),
+            RestartBlocker::Backoff { remaining } => {
+                write!(f, "in backoff: {}s remaining", remaining.as_secs())
+            }
+            RestartBlocker::StackThrottled { remaining } => {
+                write!(
+                    f,
+                    "stack restarted recently, next allowed in {}s",
+                    remaining.as_secs()
+                )
+            }
+            RestartBlocker::ComposeFileMissing => write!(f, "compose file is missing"),
+            RestartBlocker::Maintenance => write!(f, "maintenance mode"),
+            RestartBlocker::HostPressure => write!(f, "host under pressure"),
+        }
+    }
+}
+
+/// How a stack restart is carried out.
+#[derive(Clone, Copy)]
+enum RestartTarget<'a> {
+    /// `podman pod restart` of the pod all containers share
+    Pod(&'a str),
+    /// `podman start` (or `restart` if running) of each existing container
+    Containers(&'a [String]),
+    /// A container's `custom_restart_command`
+    Custom {
+        container: &'a str,
+        command: &'a str,
+    },
+    /// Compose down/up, or `podman play kube --replace`
+    Stack(SourceKind),
+}
+
+/// Outcome of checking a restarted container.
+enum Verification {
+    Started,
+    /// Not conclusive yet; checked again each cycle until `window` has passed
+    Pending {
+        reason: &'static str,
+        window: Duration,
+    },
+    Failed {
+        reason: String,
+    },
+}
+
+/// How long a `health_probe` may take before the container counts as down.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delay before re-evaluating a container whose start verification is pending.
+const PENDING_RECHECK: Duration = Duration::from_secs(5);
+
+/// How long a check cycle waits after the first container comes due, so the
+/// containers due (or dying) right after it share its `podman ps`.
+const CHECK_BATCH_WINDOW: Duration = Duration::from_secs(2);
+
+/// Down containers named individually in the periodic status line.
+const STATUS_DOWN_LIST_MAX: usize = 20;
+
+/// Check intervals without a successful cycle before `/healthz` fails.
+const HEALTHZ_MISSED_CHECKS: u64 = 3;
+
+/// Rebuilds `timer` when its configured period changed. The new timer first
+/// fires one full period from now, so a change never causes an immediate
+/// extra tick. Returns whether the timer was rebuilt.
+fn reschedule(timer: &mut Interval, seconds: u64, name: &str) -> bool {
+    let period = Duration::from_secs(seconds);
+    if timer.period() == period {
+        return false;
+    }
+    info!("{} interval changed to {}s", name, seconds);
+    *timer = interval_at(Instant::now() + period, period);
+    true
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86_400,
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {}s", minutes, seconds)
+    }
+}
+
+/// Writes the report next to the target and renames it into place, so readers
+/// never observe a partially written file.
+fn write_status_file(path: &Path, report: &StatusReport) -> Result<()> {
+    let json = to_json(report)?;
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    fs::write(&tmp_path, json + "\n")
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to move {} into place", tmp_path.display()))
+}
+
+async fn tick_optional(timer: &mut Option<Interval>) -> Option<()> {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+            Some(())
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Whether any address `address` resolves to accepts a TCP connection, each
+/// within `PROBE_TIMEOUT`.
+async fn tcp_reachable(address: &str) -> bool {
+    let addrs = match timeout(PROBE_TIMEOUT, lookup_host(address)).await {
+        Ok(Ok(addrs)) => addrs,
+        Ok(Err(e)) => {
+            debug!("Could not resolve {}: {}", address, e);
+            return false;
+        }
+        Err(_) => {
+            debug!("Resolving {} timed out", address);
+            return false;
+        }
+    };
+    for addr in addrs {
+        if let Ok(Ok(_)) = timeout(PROBE_TIMEOUT, TcpStream::connect(addr)).await {
+            return true;
+        }
+    }
+    false
+}
+
+/// An image ID shortened the way podman prints it.
+fn short_id(id: &str) -> &str {
+    let id = id.strip_prefix("sha256:").unwrap_or(id);
+    id.get(..12).unwrap_or(id)
+}
+
+async fn recv_optional<T>(rx: &mut Option<mpsc::Receiver<T>>) -> Option<T> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn recv_unbounded<T>(rx: &mut Option<mpsc::UnboundedReceiver<T>>) -> Option<T> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::runner::{CommandOutput, Invocation, MockRunner};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// What the mocked podman reports: the running containers, and whether
+    /// `podman-compose up` brings `web` back.
+    #[derive(Default)]
+    struct Host {
+        running: Mutex<HashSet<String>>,
+        starts: AtomicBool,
+    }
+
+    impl Host {
+        fn respond(&self, invocation: &Invocation) -> CommandOutput {
+            let args = invocation.arg_strings();
+            let mut running = self.running.lock().unwrap();
+            match invocation.program.as_str() {
+                "podman" if args.first().is_some_and(|arg| arg == "ps") => {
+                    let names: Vec<&str> = running.iter().map(String::as_str).collect();
+                    CommandOutput::success(names.join("\n"))
+                }
+                "podman-compose" if args.iter().any(|arg| arg == "up") => {
+                    if self.starts.load(Ordering::SeqCst) {
+                        running.insert("web".to_string());
+                    }
+                    CommandOutput::success("")
+                }
+                _ => CommandOutput::success(""),
+            }
+        }
+    }
+
+    /// A monitor of one compose file defining `web`, which is stopped.
+    struct Harness {
+        monitor: ContainerMonitor,
+        host: Arc<Host>,
+        runner: Arc<MockRunner>,
+        clock: Arc<MockClock>,
+    }
+
+    impl Harness {
+        /// `settings` is appended to the config file after `compose_files`.
+        async fn new(test: &str, settings: &str) -> Self {
+            Self::with_clock(test, settings, MockClock::new()).await
+        }
+
+        async fn with_clock(test: &str, settings: &str, clock: MockClock) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("rusty-podmon-test-{}", std::process::id()))
+                .join(test);
+            fs::create_dir_all(&dir).unwrap();
+            let compose_file = dir.join("compose.yml");
+            fs::write(
+                &compose_file,
+                "services:\n  web:\n    image: nginx\n    container_name: web\n",
+            )
+            .unwrap();
+            let config_file = dir.join("monitor.toml");
+            fs::write(
+                &config_file,
+                format!(
+                    "compose_files = [\"{}\"]\ncompose_command = \"podman-compose\"\n{}",
+                    compose_file.display(),
+                    settings
+                ),
+            )
+            .unwrap();
+
+            let host = Arc::new(Host::default());
+            let runner = Arc::new(MockRunner::new({
+                let host = Arc::clone(&host);
+                move |invocation| Ok(host.respond(invocation))
+            }));
+            let clock = Arc::new(clock);
+            let config = Config::from_file(&config_file).unwrap();
+            let mut monitor = ContainerMonitor::with_runner(config, config_file, runner.clone())
+                .unwrap()
+                .with_clock(clock.clone());
+            monitor.discover_containers().await.unwrap();
+            Self {
+                monitor,
+                host,
+                runner,
+                clock,
+            }
+        }
+
+        /// Runs a check cycle in which `web` is due.
+        async fn check(&mut self) {
+            let due = HashSet::from(["web".to_string()]);
+            self.monitor
+                .check_and_restart_containers(&due)
+                .await
+                .unwrap();
+        }
+
+        /// How many times the stack was brought up.
+        fn restarts(&self) -> usize {
+            self.runner
+                .calls()
+                .iter()
+                .filter(|call| call[0] == "podman-compose" && call.iter().any(|arg| arg == "up"))
+                .count()
+        }
+
+        fn web(&self) -> &ContainerState {
+            &self.monitor.state.managed_containers["web"]
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stopped_container_is_restarted() {
+        let mut harness = Harness::new("restarted", "").await;
+        harness.host.starts.store(true, Ordering::SeqCst);
+
+        harness.check().await;
+
+        assert_eq!(harness.restarts(), 1);
+        assert!(harness.monitor.state.is_running("web"));
+        assert_eq!(harness.web().restart_successes, 1);
+        assert_eq!(harness.web().consecutive_failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_restarts_back_off() {
+        let mut harness = Harness::new("backoff", "").await;
+
+        harness.check().await;
+        assert_eq!(harness.restarts(), 1);
+        assert_eq!(harness.web().consecutive_failures, 1);
+
+        // Still within the 2s backoff of the first failure
+        harness.check().await;
+        assert_eq!(harness.restarts(), 1);
+
+        harness.clock.advance(Duration::from_secs(2));
+        harness.check().await;
+        assert_eq!(harness.restarts(), 2);
+        assert_eq!(harness.web().consecutive_failures, 2);
+
+        // The backoff doubled
+        harness.clock.advance(Duration::from_secs(1));
+        harness.check().await;
+        assert_eq!(harness.restarts(), 2);
+
+        harness.clock.advance(Duration::from_secs(4));
+        harness.host.starts.store(true, Ordering::SeqCst);
+        harness.check().await;
+        assert_eq!(harness.restarts(), 3);
+        assert_eq!(harness.web().consecutive_failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn start_grace_defers_the_verdict_until_it_ends() {
+        let grace = "[container_overrides.web]\nstart_grace_seconds = 30\n";
+        let mut harness = Harness::new("grace-expires", grace).await;
+
+        harness.check().await;
+        assert!(harness.web().awaiting_start);
+        assert_eq!(harness.web().consecutive_failures, 0);
+
+        harness.clock.advance(Duration::from_secs(20));
+        harness.check().await;
+        assert!(harness.web().awaiting_start);
+        assert_eq!(harness.restarts(), 1);
+
+        harness.clock.advance(Duration::from_secs(10));
+        harness.check().await;
+        assert!(!harness.web().awaiting_start);
+        assert_eq!(harness.web().consecutive_failures, 1);
+        assert_eq!(harness.restarts(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn container_up_within_its_start_grace_counts_as_restarted() {
+        let grace = "[container_overrides.web]\nstart_grace_seconds = 30\n";
+        let mut harness = Harness::new("grace-met", grace).await;
+
+        harness.check().await;
+        assert!(harness.web().awaiting_start);
+
+        harness.clock.advance(Duration::from_secs(20));
+        harness
+            .host
+            .running
+            .lock()
+            .unwrap()
+            .insert("web".to_string());
+        harness.check().await;
+        assert!(!harness.web().awaiting_start);
+        assert_eq!(harness.web().restart_successes, 1);
+        assert_eq!(harness.web().consecutive_failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cycles_run_for_other_containers_do_not_count_down_checks() {
+        let mut harness = Harness::new("down-checks", "down_confirmations = 2\n").await;
+
+        harness.check().await;
+        assert_eq!(harness.web().consecutive_down_checks, 1);
+
+        // An expedited cycle for another container, with web not yet due
+        harness.clock.advance(Duration::from_secs(1));
+        harness
+            .monitor
+            .check_and_restart_containers(&HashSet::from(["db".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(harness.web().consecutive_down_checks, 1);
+        assert_eq!(harness.restarts(), 0);
+
+        harness.clock.advance(Duration::from_secs(30));
+        harness.check().await;
+        assert_eq!(harness.restarts(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn quiet_hours_follow_the_monitor_clock() {
+        let quiet = "quiet_hours = [\"02:00-04:00\"]\nquiet_hours_utc_offset = \"+01:00\"\n";
+        // 02:30 at +01:00
+        let clock = MockClock::at(SystemTime::UNIX_EPOCH + Duration::from_secs(90 * 60));
+        let mut harness = Harness::with_clock("quiet-hours", quiet, clock).await;
+        harness.host.starts.store(true, Ordering::SeqCst);
+
+        harness.check().await;
+        assert_eq!(harness.restarts(), 0);
+        assert!(matches!(
+            harness.monitor.restart_blocker("web", harness.web()),
+            Some(RestartBlocker::QuietHours)
+        ));
+
+        harness.clock.advance(Duration::from_secs(90 * 60));
+        harness.check().await;
+        assert_eq!(harness.restarts(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stack_restarts_are_rate_limited() {
+        let limit = "min_stack_restart_interval_seconds = 60\n";
+        let mut harness = Harness::new("rate-limit", limit).await;
+        harness.host.starts.store(true, Ordering::SeqCst);
+
+        harness.check().await;
+        assert_eq!(harness.restarts(), 1);
+
+        // Down again right after its backoff, but within the interval
+        harness.host.running.lock().unwrap().clear();
+        harness.clock.advance(Duration::from_secs(5));
+        harness.check().await;
+        assert_eq!(harness.restarts(), 1);
+
+        harness.clock.advance(Duration::from_secs(55));
+        harness.check().await;
+        assert_eq!(harness.restarts(), 2);
+        assert_eq!(harness.web().restart_successes, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spaced_restarts_are_queued_instead_of_run_inline() {
+        let mut harness = Harness::new("queued", "restart_spacing_seconds = 60\n").await;
+        let mut ready = harness.monitor.start_restart_queue().unwrap();
+        harness.host.starts.store(true, Ordering::SeqCst);
+
+        harness.check().await;
+        assert_eq!(harness.restarts(), 0);
+        let explanation = harness.monitor.explain("web").body;
+        assert!(explanation.contains("already queued"), "{}", explanation);
+
+        // Already queued, so not queued twice
+        harness.check().await;
+        let ReadyRestart { job, done } = ready.recv().await.unwrap();
+        harness.monitor.run_queued_restart(job).await;
+        done.send(()).unwrap();
+        assert_eq!(harness.restarts(), 1);
+        assert_eq!(harness.web().restart_successes, 1);
+        assert!(ready.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queued_restart_is_dropped_once_the_container_is_back() {
+        let mut harness = Harness::new("queued-recovered", "restart_spacing_seconds = 60\n").await;
+        let mut ready = harness.monitor.start_restart_queue().unwrap();
+
+        harness.check().await;
+        harness
+            .host
+            .running
+            .lock()
+            .unwrap()
+            .insert("web".to_string());
+        harness.check().await;
+
+        let ReadyRestart { job, .. } = ready.recv().await.unwrap();
+        harness.monitor.run_queued_restart(job).await;
+        assert_eq!(harness.restarts(), 0);
+    }
+
+    #[tokio::test]
+    async fn tcp_probe_connects_without_blocking() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        assert!(tcp_reachable(&address).await);
+
+        drop(listener);
+        assert!(!tcp_reachable(&address).await);
+        assert!(!tcp_reachable("no port").await);
     }
 }
@@ -0,0 +1,894 @@
+use crate::cli_config::{Config, NotifierConfig};
+use crate::metrics::Metrics;
+use crate::runner::{CommandRunner, Invocation, run_logged};
+use crate::state::RestartReason;
+
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::Notify;
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+// =============================================================================
+// Notification Subsystem
+// =============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    RestartSucceeded,
+    RestartFailed,
+    GaveUp,
+    /// Observed down in `monitor` mode (no restart is attempted)
+    ContainerDown,
+    /// Observed running again after a `ContainerDown`
+    ContainerRecovered,
+    /// The share of running managed containers fell below
+    /// `min_running_percent` (a fleet-wide event, with no container)
+    FleetDegraded,
+    /// The share of running managed containers is back at or above
+    /// `min_running_percent`
+    FleetRecovered,
+    /// Summary of the events of one `notification_digest_seconds` window
+    Digest,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::RestartSucceeded => "restart_succeeded",
+            EventKind::RestartFailed => "restart_failed",
+            EventKind::GaveUp => "gave_up",
+            EventKind::ContainerDown => "container_down",
+            EventKind::ContainerRecovered => "container_recovered",
+            EventKind::FleetDegraded => "fleet_degraded",
+            EventKind::FleetRecovered => "fleet_recovered",
+            EventKind::Digest => "digest",
+        }
+    }
+
+    /// Message used when no `notification_template` is configured.
+    pub fn default_template(&self) -> &'static str {
+        match self {
+            EventKind::RestartSucceeded => {
+                "{event}: {container} restarted at {timestamp} (compose: {compose_file}, reason: {reason}, restarts: {restarts})"
+            }
+            EventKind::RestartFailed | EventKind::GaveUp => {
+                "{event}: {container} at {timestamp} (compose: {compose_file}, reason: {reason}, failures: {failures}/{max_failures})"
+            }
+            EventKind::ContainerDown | EventKind::ContainerRecovered => {
+                "{event}: {container} at {timestamp} (compose: {compose_file})"
+            }
+            EventKind::FleetDegraded | EventKind::FleetRecovered => {
+                "{event}: {running}/{managed} managed containers running at {timestamp}"
+            }
+            EventKind::Digest => {
+                "{event}: {summary} ({running}/{managed} managed containers running)"
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RestartEvent {
+    /// Empty for fleet-wide events.
+    pub container: String,
+    /// Empty for fleet-wide events.
+    pub compose_file: PathBuf,
+    pub kind: EventKind,
+    /// Trigger of the restart, for restart events only.
+    pub reason: Option<RestartReason>,
+    /// For digests: successful restarts in the window.
+    pub restart_count: u32,
+    /// For digests: failed restarts (and give-ups) in the window.
+    pub consecutive_failures: u32,
+    /// Failure cap for this container; 0 when it is never given up on.
+    pub max_failures: u32,
+    /// Managed containers running, and managed in total, when the event fired.
+    pub running: usize,
+    pub managed: usize,
+    pub timestamp: SystemTime,
+    /// Human-readable summary; empty except for digests.
+    pub summary: String,
+}
+
+impl RestartEvent {
+    /// The restart reason, or an empty string when there is none.
+    pub fn reason_str(&self) -> &'static str {
+        self.reason.map_or("", |reason| reason.as_str())
+    }
+
+    /// The notification text: `template` (a validated
+    /// `notification_template`) or the event kind's default, rendered.
+    pub fn message(&self, template: Option<&str>) -> String {
+        render_template(template.unwrap_or(self.kind.default_template()), self)
+    }
+
+    fn placeholder(&self, name: &str) -> Option<String> {
+        Some(match name {
+            "event" => self.kind.as_str().to_string(),
+            "container" => self.container.clone(),
+            "compose_file" => self.compose_file.display().to_string(),
+            "reason" => self.reason_str().to_string(),
+            "restarts" => self.restart_count.to_string(),
+            "failures" => self.consecutive_failures.to_string(),
+            "max_failures" => self.max_failures.to_string(),
+            "running" => self.running.to_string(),
+            "managed" => self.managed.to_string(),
+            "timestamp" => format_timestamp(self.timestamp),
+            "summary" => self.summary.clone(),
+            _ => return None,
+        })
+    }
+}
+
+/// Placeholders a `notification_template` may use.
+pub const TEMPLATE_PLACEHOLDERS: &[&str] = &[
+    "event",
+    "container",
+    "compose_file",
+    "reason",
+    "restarts",
+    "failures",
+    "max_failures",
+    "running",
+    "managed",
+    "timestamp",
+    "summary",
+];
+
+/// Checks that a `notification_template` only uses known placeholders and
+/// that its braces are balanced (`{{` and `}}` are literal braces).
+pub fn validate_template(template: &str) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        let after = &rest[start + 1..];
+        if rest[start..].starts_with("{{") || rest[start..].starts_with("}}") {
+            rest = &after[1..];
+            continue;
+        }
+        if rest[start..].starts_with('}') {
+            anyhow::bail!("Unmatched '}}' in notification_template: {}", template);
+        }
+        let end = after
+            .find('}')
+            .with_context(|| format!("Unclosed '{{' in notification_template: {}", template))?;
+        let name = &after[..end];
+        if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+            anyhow::bail!(
+                "Unknown placeholder {{{}}} in notification_template; available: {}",
+                name,
+                TEMPLATE_PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{}}}", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        rest = &after[end + 1..];
+    }
+    Ok(())
+}
+
+/// Replaces each `{placeholder}` in `template` with the event's value.
+/// Unknown placeholders are kept as written; `validate_template` rejects
+/// them in configured templates.
+fn render_template(template: &str, event: &RestartEvent) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        output.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            output.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let field = tail[1..]
+            .find('}')
+            .and_then(|end| Some((event.placeholder(&tail[1..end + 1])?, end + 2)));
+        match field {
+            Some((value, consumed)) => {
+                output.push_str(&value);
+                rest = &tail[consumed..];
+            }
+            None => {
+                output.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+pub fn format_timestamp(timestamp: SystemTime) -> String {
+    OffsetDateTime::from(timestamp)
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub type NotifyFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// A sink for restart events. Sinks configured by the user are driven from a
+/// background worker (see `QueuedNotifier`), so they may be slow or fail.
+pub trait Notifier: Send + Sync {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a>;
+}
+
+/// Discards every event. Used when no notifiers are configured.
+pub struct NullNotifier;
+
+impl Notifier for NullNotifier {
+    fn notify<'a>(&'a self, _event: &'a RestartEvent) -> NotifyFuture<'a> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Keeps every event it receives, for assertions. Clones share the same
+/// record, so a test can keep one while the monitor owns another.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingNotifier {
+    events: Arc<Mutex<Vec<RestartEvent>>>,
+}
+
+impl RecordingNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The events received so far, oldest first.
+    pub fn events(&self) -> Vec<RestartEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// The kinds of the events received so far, oldest first.
+    pub fn kinds(&self) -> Vec<EventKind> {
+        self.events().iter().map(|event| event.kind).collect()
+    }
+}
+
+impl Notifier for RecordingNotifier {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Writes events to the monitor's own log, rendered with the
+/// `notification_template` or the event kind's default template.
+pub struct LogNotifier {
+    template: Option<String>,
+}
+
+impl LogNotifier {
+    pub fn new(template: Option<String>) -> Self {
+        Self { template }
+    }
+}
+
+impl Notifier for LogNotifier {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let message = event.message(self.template.as_deref());
+            match event.kind {
+                EventKind::RestartFailed | EventKind::GaveUp | EventKind::ContainerDown => {
+                    warn!("[notify] {}", message)
+                }
+                EventKind::Digest if event.consecutive_failures > 0 => {
+                    warn!("[notify] {}", message)
+                }
+                EventKind::FleetDegraded => error!("[notify] {}", message),
+                EventKind::RestartSucceeded
+                | EventKind::ContainerRecovered
+                | EventKind::FleetRecovered
+                | EventKind::Digest => info!("[notify] {}", message),
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Runs the user's `on_restart_command` / `on_failure_command` hooks. Each
+/// command is a shell snippet run with `sh -c`, so pipes and redirects work.
+/// Event details are passed only through `PODMON_*` environment variables and
+/// are never interpolated into the command text.
+pub struct HookNotifier {
+    on_restart: Option<String>,
+    on_failure: Option<String>,
+    timeout: Duration,
+    /// Renders `PODMON_MESSAGE`
+    template: Option<String>,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl HookNotifier {
+    pub fn new(
+        on_restart: Option<String>,
+        on_failure: Option<String>,
+        timeout: Duration,
+        template: Option<String>,
+        runner: Arc<dyn CommandRunner>,
+    ) -> Self {
+        Self {
+            on_restart,
+            on_failure,
+            timeout,
+            template,
+            runner,
+        }
+    }
+}
+
+impl Notifier for HookNotifier {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let command = match event.kind {
+                EventKind::RestartSucceeded => self.on_restart.as_ref(),
+                EventKind::RestartFailed | EventKind::GaveUp => self.on_failure.as_ref(),
+                EventKind::Digest if event.consecutive_failures > 0 => self.on_failure.as_ref(),
+                EventKind::Digest if event.restart_count > 0 => self.on_restart.as_ref(),
+                EventKind::Digest => None,
+                EventKind::ContainerDown
+                | EventKind::ContainerRecovered
+                | EventKind::FleetDegraded
+                | EventKind::FleetRecovered => None,
+            };
+            let Some(command) = command else {
+                return Ok(());
+            };
+
+            let subject = if event.container.is_empty() {
+                event.kind.as_str()
+            } else {
+                event.container.as_str()
+            };
+            debug!(
+                "Running {} hook for {}: {}",
+                event.kind.as_str(),
+                subject,
+                command
+            );
+
+            let mut invocation = Invocation::new("sh", self.runner.clone());
+            invocation
+                .args(["-c", command])
+                .env("PODMON_EVENT", event.kind.as_str())
+                .env("PODMON_CONTAINER", &event.container)
+                .env(
+                    "PODMON_COMPOSE_FILE",
+                    &event.compose_file.display().to_string(),
+                )
+                .env("PODMON_REASON", event.reason_str())
+                .env("PODMON_RESTART_COUNT", &event.restart_count.to_string())
+                .env(
+                    "PODMON_CONSECUTIVE_FAILURES",
+                    &event.consecutive_failures.to_string(),
+                )
+                .env("PODMON_MAX_FAILURES", &event.max_failures.to_string())
+                .env("PODMON_RUNNING", &event.running.to_string())
+                .env("PODMON_MANAGED", &event.managed.to_string())
+                .env("PODMON_TIMESTAMP", &format_timestamp(event.timestamp))
+                .env("PODMON_SUMMARY", &event.summary)
+                .env("PODMON_MESSAGE", &event.message(self.template.as_deref()));
+            run_logged(&invocation, "Hook", "hook", subject, self.timeout).await
+        })
+    }
+}
+
+/// Fans each event out to all wrapped notifiers. A failing notifier does not
+/// prevent the others from receiving the event.
+pub struct CompositeNotifier {
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl CompositeNotifier {
+    pub fn new(notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        Self { notifiers }
+    }
+}
+
+impl Notifier for CompositeNotifier {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let mut first_error = None;
+            for notifier in &self.notifiers {
+                if let Err(e) = notifier.notify(event).await {
+                    warn!("Notifier failed for {}: {:#}", event.container, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+            first_error.map_or(Ok(()), Err)
+        })
+    }
+}
+
+/// Retries a failing notifier with exponential backoff (1s, 2s, 4s, ...).
+pub struct RetryingNotifier {
+    inner: Box<dyn Notifier>,
+    max_attempts: u32,
+}
+
+impl RetryingNotifier {
+    pub fn new(inner: Box<dyn Notifier>, max_attempts: u32) -> Self {
+        Self {
+            inner,
+            max_attempts: max_attempts.max(1),
+        }
+    }
+}
+
+impl Notifier for RetryingNotifier {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a> {
+        Box::pin(async move {
+            let mut attempt = 1;
+            loop {
+                match self.inner.notify(event).await {
+                    Ok(()) => return Ok(()),
+                    Err(e) if attempt >= self.max_attempts => return Err(e),
+                    Err(e) => {
+                        let delay = Duration::from_secs(2_u64.pow((attempt - 1).min(6)));
+                        debug!(
+                            "Notification attempt {}/{} for {} failed, retrying in {}s: {:#}",
+                            attempt,
+                            self.max_attempts,
+                            event.container,
+                            delay.as_secs(),
+                            e
+                        );
+                        sleep(delay).await;
+                        attempt += 1;
+                    }
+                }
+            }
+        })
+    }
+}
+
+struct NotificationQueue {
+    events: Mutex<VecDeque<RestartEvent>>,
+    capacity: usize,
+    ready: Notify,
+    /// Counts dropped events as `podmon_notifications_dropped_total`
+    metrics: Metrics,
+}
+
+impl NotificationQueue {
+    fn new(capacity: usize, metrics: Metrics) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+            ready: Notify::new(),
+            metrics,
+        }
+    }
+
+    fn push(&self, event: RestartEvent) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        if events.len() >= self.capacity
+            && let Some(oldest) = events.pop_front()
+        {
+            let dropped = self.metrics.count_dropped_notification();
+            warn!(
+                "Notification queue full, dropped {} event for {} ({} dropped total)",
+                oldest.kind.as_str(),
+                oldest.container,
+                dropped
+            );
+        }
+        events.push_back(event);
+        drop(events);
+        self.ready.notify_one();
+    }
+
+    fn pop(&self) -> Option<RestartEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+    }
+}
+
+/// Hands events to a dedicated worker task so a slow or unreachable sink never
+/// stalls the monitor loop. The queue is bounded; when it is full the oldest
+/// event is dropped rather than blocking the producer, which a plain
+/// `tokio::sync::mpsc` channel cannot do from the sending side.
+pub struct QueuedNotifier {
+    queue: Arc<NotificationQueue>,
+}
+
+impl QueuedNotifier {
+    /// Spawns the delivery worker. Must be called from within a Tokio runtime.
+    pub fn spawn(sink: Box<dyn Notifier>, capacity: usize, metrics: Metrics) -> Self {
+        let queue = Arc::new(NotificationQueue::new(capacity, metrics));
+
+        let worker_queue = Arc::clone(&queue);
+        tokio::spawn(async move {
+            loop {
+                match worker_queue.pop() {
+                    Some(event) => {
+                        if let Err(e) = sink.notify(&event).await {
+                            error!(
+                                "Giving up on {} notification for {}: {:#}",
+                                event.kind.as_str(),
+                                event.container,
+                                e
+                            );
+                        }
+                    }
+                    None => worker_queue.ready.notified().await,
+                }
+            }
+        });
+
+        Self { queue }
+    }
+}
+
+impl Notifier for QueuedNotifier {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a> {
+        self.queue.push(event.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Collects events for a fixed window and passes on one `Digest` event
+/// summarizing them per compose file, so a mass outage produces one message
+/// instead of dozens. Give-ups are passed on immediately instead, as they
+/// need a human, and are left out of the digest so they are not delivered
+/// (and `on_failure_command` run) twice.
+pub struct DigestNotifier {
+    inner: Arc<dyn Notifier>,
+    pending: Arc<Mutex<Vec<RestartEvent>>>,
+}
+
+impl DigestNotifier {
+    /// Spawns the task that flushes the digest every `window`. Must be
+    /// called from within a Tokio runtime.
+    pub fn spawn(inner: Box<dyn Notifier>, window: Duration) -> Self {
+        let inner: Arc<dyn Notifier> = Arc::from(inner);
+        let pending = Arc::new(Mutex::new(Vec::new()));
+
+        let flush_inner = Arc::clone(&inner);
+        let flush_pending = Arc::clone(&pending);
+        tokio::spawn(async move {
+            loop {
+                sleep(window).await;
+                let events =
+                    std::mem::take(&mut *flush_pending.lock().unwrap_or_else(|e| e.into_inner()));
+                if events.is_empty() {
+                    continue;
+                }
+                let digest = summarize(&events);
+                if let Err(e) = flush_inner.notify(&digest).await {
+                    warn!("Failed to deliver notification digest: {:#}", e);
+                }
+            }
+        });
+
+        Self { inner, pending }
+    }
+}
+
+impl Notifier for DigestNotifier {
+    fn notify<'a>(&'a self, event: &'a RestartEvent) -> NotifyFuture<'a> {
+        if event.kind == EventKind::GaveUp {
+            return self.inner.notify(event);
+        }
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Builds the digest event for a window's events, e.g. "2 restarted, 1
+/// failed in /srv/app/compose.yml; fleet degraded".
+fn summarize(events: &[RestartEvent]) -> RestartEvent {
+    const KINDS: [(EventKind, &str); 4] = [
+        (EventKind::RestartSucceeded, "restarted"),
+        (EventKind::RestartFailed, "failed"),
+        (EventKind::ContainerDown, "down"),
+        (EventKind::ContainerRecovered, "recovered"),
+    ];
+
+    let mut stacks: Vec<&PathBuf> = events
+        .iter()
+        .filter(|event| !event.container.is_empty())
+        .map(|event| &event.compose_file)
+        .collect();
+    stacks.sort();
+    stacks.dedup();
+
+    let count = |kind: EventKind, stack: Option<&PathBuf>| {
+        events
+            .iter()
+            .filter(|event| event.kind == kind && stack.is_none_or(|s| &event.compose_file == s))
+            .count()
+    };
+
+    let mut parts: Vec<String> = stacks
+        .iter()
+        .map(|stack| {
+            let counts: Vec<String> = KINDS
+                .iter()
+                .map(|&(kind, label)| (count(kind, Some(stack)), label))
+                .filter(|&(n, _)| n > 0)
+                .map(|(n, label)| format!("{} {}", n, label))
+                .collect();
+            format!("{} in {}", counts.join(", "), stack.display())
+        })
+        .collect();
+    for (kind, label) in [
+        (EventKind::FleetDegraded, "fleet degraded"),
+        (EventKind::FleetRecovered, "fleet recovered"),
+    ] {
+        if count(kind, None) > 0 {
+            parts.push(label.to_string());
+        }
+    }
+
+    let last = events.last();
+    RestartEvent {
+        container: String::new(),
+        compose_file: PathBuf::new(),
+        kind: EventKind::Digest,
+        reason: None,
+        restart_count: count(EventKind::RestartSucceeded, None) as u32,
+        consecutive_failures: count(EventKind::RestartFailed, None) as u32,
+        max_failures: 0,
+        running: last.map_or(0, |event| event.running),
+        managed: last.map_or(0, |event| event.managed),
+        timestamp: SystemTime::now(),
+        summary: parts.join("; "),
+    }
+}
+
+pub fn build_notifier(
+    config: &Config,
+    runner: Arc<dyn CommandRunner>,
+    metrics: Metrics,
+) -> Box<dyn Notifier> {
+    let has_hooks = config.on_restart_command.is_some() || config.on_failure_command.is_some();
+    if config.notifiers.is_empty() && !has_hooks {
+        return Box::new(NullNotifier);
+    }
+
+    let mut notifiers: Vec<Box<dyn Notifier>> = config
+        .notifiers
+        .iter()
+        .map(|notifier_config| -> Box<dyn Notifier> {
+            let sink: Box<dyn Notifier> = match notifier_config {
+                NotifierConfig::Log => {
+                    Box::new(LogNotifier::new(config.notification_template.clone()))
+                }
+            };
+            Box::new(RetryingNotifier::new(
+                sink,
+                config.notification_max_attempts,
+            ))
+        })
+        .collect();
+
+    // Hooks are not retried: re-running a remediation command on failure
+    // could do more harm than the missed run
+    if has_hooks {
+        info!("Restart hooks enabled; commands run via sh -c");
+        notifiers.push(Box::new(HookNotifier::new(
+            config.on_restart_command.clone(),
+            config.on_failure_command.clone(),
+            Duration::from_secs(config.hook_timeout_seconds),
+            config.notification_template.clone(),
+            runner,
+        )));
+    }
+
+    let queued = Box::new(QueuedNotifier::spawn(
+        Box::new(CompositeNotifier::new(notifiers)),
+        config.notification_queue_capacity,
+        metrics,
+    ));
+    match config.notification_digest_seconds {
+        0 => queued,
+        seconds => {
+            info!("Notifications are sent as a digest every {}s", seconds);
+            Box::new(DigestNotifier::spawn(queued, Duration::from_secs(seconds)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails every delivery.
+    struct FailingNotifier;
+
+    impl Notifier for FailingNotifier {
+        fn notify<'a>(&'a self, _event: &'a RestartEvent) -> NotifyFuture<'a> {
+            Box::pin(async { Err(anyhow::anyhow!("sink unavailable")) })
+        }
+    }
+
+    fn event(kind: EventKind) -> RestartEvent {
+        RestartEvent {
+            container: "web".to_string(),
+            compose_file: PathBuf::from("/srv/app/compose.yml"),
+            kind,
+            reason: Some(RestartReason::Down),
+            restart_count: 1,
+            consecutive_failures: 0,
+            max_failures: 5,
+            running: 1,
+            managed: 1,
+            timestamp: SystemTime::UNIX_EPOCH,
+            summary: String::new(),
+        }
+    }
+
+    #[test]
+    fn template_accepts_known_placeholders_and_escaped_braces() {
+        validate_template("{container} {event}: {{literal}} }} {{").unwrap();
+        validate_template("").unwrap();
+    }
+
+    #[test]
+    fn template_rejects_unknown_placeholders_and_stray_braces() {
+        let error = |template| validate_template(template).unwrap_err().to_string();
+        assert!(error("{nope}").starts_with("Unknown placeholder {nope}"));
+        assert!(error("down: {container").starts_with("Unclosed '{'"));
+        assert!(error("down } now").starts_with("Unmatched '}'"));
+        assert!(error("{é}").starts_with("Unknown placeholder {é}"));
+        assert!(error("café {").starts_with("Unclosed '{'"));
+    }
+
+    #[test]
+    fn template_renders_placeholders_and_literal_braces() {
+        let event = event(EventKind::RestartSucceeded);
+        let render = |template| render_template(template, &event);
+        assert_eq!(render("{container} ({reason})"), "web (down)");
+        assert_eq!(render("{{container}} }}{{"), "{container} }{");
+        // Left as written, so a bad template still says something
+        assert_eq!(render("{nope} {container"), "{nope} {container");
+        assert_eq!(render("} {"), "} {");
+        assert_eq!(render("é{container}ü {é} ü}"), "éwebü {é} ü}");
+    }
+
+    #[tokio::test]
+    async fn composite_delivers_to_every_notifier() {
+        let first = RecordingNotifier::new();
+        let second = RecordingNotifier::new();
+        let composite =
+            CompositeNotifier::new(vec![Box::new(first.clone()), Box::new(second.clone())]);
+
+        composite
+            .notify(&event(EventKind::RestartSucceeded))
+            .await
+            .unwrap();
+        composite.notify(&event(EventKind::GaveUp)).await.unwrap();
+
+        let expected = vec![EventKind::RestartSucceeded, EventKind::GaveUp];
+        assert_eq!(first.kinds(), expected);
+        assert_eq!(second.kinds(), expected);
+        assert_eq!(first.events()[0].container, "web");
+    }
+
+    #[tokio::test]
+    async fn composite_keeps_delivering_after_a_failure() {
+        let before = RecordingNotifier::new();
+        let after = RecordingNotifier::new();
+        let composite = CompositeNotifier::new(vec![
+            Box::new(before.clone()),
+            Box::new(FailingNotifier),
+            Box::new(after.clone()),
+        ]);
+
+        let result = composite.notify(&event(EventKind::RestartFailed)).await;
+
+        assert!(result.is_err());
+        assert_eq!(before.kinds(), vec![EventKind::RestartFailed]);
+        assert_eq!(after.kinds(), vec![EventKind::RestartFailed]);
+    }
+
+    #[tokio::test]
+    async fn hook_notifier_runs_the_matching_hook() {
+        use crate::runner::{CommandOutput, MockRunner};
+
+        let runner = Arc::new(MockRunner::new(|invocation| {
+            Ok(match invocation.arg_strings().last().map(String::as_str) {
+                Some("notify-ok") => CommandOutput::success(""),
+                _ => CommandOutput::failure(2, "page failed"),
+            })
+        }));
+        let hooks = HookNotifier::new(
+            Some("notify-ok".to_string()),
+            Some("page-oncall".to_string()),
+            Duration::from_secs(5),
+            None,
+            runner.clone(),
+        );
+
+        hooks
+            .notify(&event(EventKind::RestartSucceeded))
+            .await
+            .unwrap();
+        hooks
+            .notify(&event(EventKind::ContainerDown))
+            .await
+            .unwrap();
+        let error = hooks.notify(&event(EventKind::GaveUp)).await.unwrap_err();
+
+        assert_eq!(error.to_string(), "Hook for web exited with exit code 2");
+        assert_eq!(
+            runner.calls(),
+            vec![
+                vec!["sh", "-c", "notify-ok"],
+                vec!["sh", "-c", "page-oncall"],
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn digest_delivers_give_ups_once() {
+        let window = Duration::from_secs(60);
+        let recording = RecordingNotifier::new();
+        let digest = DigestNotifier::spawn(Box::new(recording.clone()), window);
+
+        digest
+            .notify(&event(EventKind::RestartFailed))
+            .await
+            .unwrap();
+        digest.notify(&event(EventKind::GaveUp)).await.unwrap();
+        assert_eq!(recording.kinds(), vec![EventKind::GaveUp]);
+
+        sleep(window + Duration::from_secs(1)).await;
+        assert_eq!(
+            recording.kinds(),
+            vec![EventKind::GaveUp, EventKind::Digest]
+        );
+        assert_eq!(
+            recording.events()[1].summary,
+            "1 failed in /srv/app/compose.yml"
+        );
+
+        // A window with only a give-up has nothing left to summarize
+        digest.notify(&event(EventKind::GaveUp)).await.unwrap();
+        sleep(window).await;
+        assert_eq!(
+            recording.kinds(),
+            vec![EventKind::GaveUp, EventKind::Digest, EventKind::GaveUp]
+        );
+    }
+
+    #[test]
+    fn full_queue_drops_the_oldest_event() {
+        let metrics = Metrics::default();
+        let queue = NotificationQueue::new(2, metrics.clone());
+
+        queue.push(event(EventKind::ContainerDown));
+        queue.push(event(EventKind::RestartFailed));
+        queue.push(event(EventKind::GaveUp));
+
+        assert_eq!(queue.pop().map(|e| e.kind), Some(EventKind::RestartFailed));
+        assert_eq!(queue.pop().map(|e| e.kind), Some(EventKind::GaveUp));
+        assert!(queue.pop().is_none());
+        assert!(
+            metrics
+                .render()
+                .contains("\npodmon_notifications_dropped_total 1\n")
+        );
+    }
+}
@@ -1,32 +1,173 @@
 use anyhow::{Context, Result};
-use serde_yml::Value;
+use serde::{Deserialize, Serialize};
+use serde_yml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
-use tracing::debug;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
 
 // =============================================================================
 // Compose File Parser
 // =============================================================================
 
+/// Which tool owns a container, and therefore how it is restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    /// A compose service, restarted with `podman-compose down/up`
+    Compose,
+    /// A container from Kubernetes YAML, restarted with `podman play kube`
+    Kube,
+}
+
+/// The compose `restart` policy (or Kubernetes `restartPolicy`) of a
+/// container. Services with `restart: "no"` are never managed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Always,
+    UnlessStopped,
+    OnFailure,
+}
+
+impl RestartPolicy {
+    fn from_compose(policy: &str) -> Self {
+        match policy {
+            "always" => RestartPolicy::Always,
+            p if p.starts_with("on-failure") => RestartPolicy::OnFailure,
+            _ => RestartPolicy::UnlessStopped,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ContainerSpec {
+    pub source: SourceKind,
+    pub restart_policy: RestartPolicy,
     pub name: String,
+    pub service: String,
+    pub project: Option<String>,
+    /// True when the name came from `container_name` rather than a template.
+    pub explicit_name: bool,
+    /// Replica number (1-based) of a scaled service; 1 for everything else.
+    pub index: u32,
+    /// Failure cap from the `rusty-podmon.max-failures` label or
+    /// `restart: on-failure:N`.
+    pub max_retries: Option<u32>,
+    /// Restart priority from the `rusty-podmon.priority` label.
+    pub priority: Option<i32>,
+    pub healthcheck: Option<HealthcheckSpec>,
+    /// The `image` a service runs; `None` for `build:`-only services.
+    pub image: Option<String>,
+    /// Matches the live name of a template-named container when the exact
+    /// name differs (see `NamePattern`); `None` for exact matching.
+    pub name_pattern: Option<NamePattern>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthcheckSpec {
+    pub start_period: Option<Duration>,
+}
+
+/// What the parser decided about one service (or Kubernetes container), kept
+/// for the `--once-per-file` discovery dump.
+#[derive(Debug, Serialize)]
+pub struct ServiceDecision {
+    pub service: String,
+    /// `container_name` as written, before interpolation
+    pub declared_name: Option<String>,
+    pub restart_policy: String,
+    /// Resolved names, one per replica; empty when skipped
+    pub container_names: Vec<String>,
+    pub included: bool,
+    /// Why the service was skipped, or a caveat about how it is included
+    pub reason: Option<String>,
+}
+
+/// Everything parsed from one file.
+#[derive(Debug, Default)]
+pub struct ParsedFile {
+    pub containers: Vec<ContainerSpec>,
+    pub decisions: Vec<ServiceDecision>,
+}
+
+/// Settings that influence how container names are resolved.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Template for containers without an explicit `container_name`.
+    /// Supports `{project}`, `{service}` and `{index}` placeholders.
+    pub name_template: String,
+    /// Merge a sibling `<name>.override.yml` on top, as compose does.
+    pub merge_overrides: bool,
+    /// Give template-named containers a `NamePattern`.
+    pub name_patterns: bool,
+    /// Replica counts by service name, overriding the compose file's.
+    pub scale: BTreeMap<String, u32>,
 }
 
+/// Service label that excludes a service from monitoring when falsy.
+pub const LABEL_ENABLED: &str = "rusty-podmon.enabled";
+/// Service label overriding the consecutive failure cap.
+pub const LABEL_MAX_FAILURES: &str = "rusty-podmon.max-failures";
+/// Service label setting restart priority (higher restarts first).
+pub const LABEL_PRIORITY: &str = "rusty-podmon.priority";
+
+/// Project name for compose files without a directory to name it after.
+pub const DEFAULT_PROJECT_NAME: &str = "default";
+
 pub struct ComposeParser;
 
 impl ComposeParser {
-    pub fn parse_containers(file_path: &Path) -> Result<Vec<ContainerSpec>> {
+    pub fn parse_containers(
+        file_path: &Path,
+        options: &ParseOptions,
+    ) -> Result<Vec<ContainerSpec>> {
+        Self::parse_file(file_path, options).map(|parsed| parsed.containers)
+    }
+
+    /// Like `parse_containers`, but also reports the decision taken for
+    /// every service, including the skipped ones.
+    pub fn parse_file(file_path: &Path, options: &ParseOptions) -> Result<ParsedFile> {
         let content = fs::read_to_string(file_path)
             .with_context(|| format!("Failed to read compose file: {}", file_path.display()))?;
 
-        let yaml: Value = serde_yml::from_str(&content)
+        let documents = parse_documents(&content)
             .with_context(|| format!("Failed to parse YAML: {}", file_path.display()))?;
 
-        let mut containers = Vec::new();
+        if documents.iter().any(KubeParser::is_kube_document) {
+            debug!("Treating {} as Kubernetes YAML", file_path.display());
+            return KubeParser::parse_file(&documents);
+        }
+
+        let mut yaml = documents.into_iter().next().unwrap_or(Value::Null);
+        if options.merge_overrides
+            && let Some(override_path) = Self::find_override(file_path)
+        {
+            let content = fs::read_to_string(&override_path).with_context(|| {
+                format!("Failed to read override file: {}", override_path.display())
+            })?;
+            let overlay: Value = serde_yml::from_str(&content)
+                .with_context(|| format!("Failed to parse YAML: {}", override_path.display()))?;
+            if overlay.is_mapping() {
+                debug!(
+                    "Merging {} over {}",
+                    override_path.display(),
+                    file_path.display()
+                );
+                merge_yaml(&mut yaml, overlay);
+            } else {
+                warn!(
+                    "Ignoring override file {}: not a mapping",
+                    override_path.display()
+                );
+            }
+        }
+        let mut parsed = ParsedFile::default();
+        let base_dir = file_path.parent().unwrap_or(Path::new("."));
+        let dotenv = load_env_file(&base_dir.join(".env")).unwrap_or_default();
 
-        if let Some(services) = yaml.get("services").and_then(|s| s.as_mapping()) {
-            for (service_name, service_config) in services {
+        if let Some(services) = Self::services(&yaml, file_path) {
+            let project = Self::project_name(file_path, &dotenv);
+            for (service_name, service_config) in &services {
                 let service_name_str = service_name
                     .as_str()
                     .context("Service name is not a valid string")?;
@@ -36,32 +177,900 @@ impl ComposeParser {
                     .get("restart")
                     .and_then(|r| r.as_str())
                     .unwrap_or("unless-stopped");
+                let declared_name = service_config
+                    .get("container_name")
+                    .and_then(|name| name.as_str())
+                    .map(String::from);
+                let mut decision = ServiceDecision {
+                    service: service_name_str.to_string(),
+                    declared_name: declared_name.clone(),
+                    restart_policy: restart_policy.to_string(),
+                    container_names: Vec::new(),
+                    included: false,
+                    reason: None,
+                };
 
                 if restart_policy == "no" {
                     debug!("Skipping {} - restart policy is 'no'", service_name_str);
+                    decision.reason = Some("restart policy is \"no\"".to_string());
+                    parsed.decisions.push(decision);
                     continue;
                 }
 
-                let container_name = service_config
-                    .get("container_name")
-                    .and_then(|name| name.as_str())
-                    .map(String::from)
-                    .unwrap_or_else(|| {
-                        Self::generate_default_name(file_path, service_name_str)
-                            .unwrap_or_else(|| service_name_str.to_string())
+                let labels = Self::parse_labels(service_config);
+                if labels
+                    .get(LABEL_ENABLED)
+                    .is_some_and(|enabled| !is_truthy(enabled))
+                {
+                    debug!(
+                        "Skipping {} - disabled via {} label",
+                        service_name_str, LABEL_ENABLED
+                    );
+                    decision.reason = Some(format!("disabled via {} label", LABEL_ENABLED));
+                    parsed.decisions.push(decision);
+                    continue;
+                }
+
+                // Failure cap: label first, then restart: "on-failure:N"
+                let max_retries = labels
+                    .get(LABEL_MAX_FAILURES)
+                    .and_then(|count| count.trim().parse().ok())
+                    .or_else(|| {
+                        restart_policy
+                            .strip_prefix("on-failure:")
+                            .and_then(|count| count.trim().parse().ok())
                     });
 
-                containers.push(ContainerSpec {
-                    name: container_name,
+                let priority = labels
+                    .get(LABEL_PRIORITY)
+                    .and_then(|priority| priority.trim().parse().ok());
+
+                let declared_image = service_config.get("image").and_then(|i| i.as_str());
+                let vars = if declared_name.is_some() || declared_image.is_some() {
+                    Self::interpolation_vars(service_config, base_dir, &dotenv)
+                } else {
+                    HashMap::new()
+                };
+                let explicit_name = declared_name
+                    .as_deref()
+                    .map(|name| interpolate(name, &vars));
+                let image = declared_image.map(|image| interpolate(image, &vars));
+
+                let mut replicas = options
+                    .scale
+                    .get(service_name_str)
+                    .copied()
+                    .unwrap_or_else(|| Self::parse_replicas(service_config));
+                if replicas == 0 {
+                    debug!("Skipping {} - scaled to 0 replicas", service_name_str);
+                    decision.reason = Some("scaled to 0 replicas".to_string());
+                    parsed.decisions.push(decision);
+                    continue;
+                }
+                if replicas > 1 && explicit_name.is_some() {
+                    // Compose refuses to scale a service with a fixed name
+                    warn!(
+                        "Service {} sets container_name and {} replicas; monitoring a single container",
+                        service_name_str, replicas
+                    );
+                    decision.reason = Some(format!(
+                        "sets container_name and {} replicas; monitoring a single container",
+                        replicas
+                    ));
+                    replicas = 1;
+                }
+
+                let healthcheck = service_config
+                    .get("healthcheck")
+                    .and_then(Self::parse_healthcheck);
+
+                for index in 1..=replicas {
+                    let container_name = explicit_name.clone().unwrap_or_else(|| {
+                        render_name_template(
+                            &options.name_template,
+                            &project,
+                            service_name_str,
+                            index,
+                        )
+                    });
+                    decision.container_names.push(container_name.clone());
+
+                    parsed.containers.push(ContainerSpec {
+                        source: SourceKind::Compose,
+                        restart_policy: RestartPolicy::from_compose(restart_policy),
+                        name: container_name,
+                        service: service_name_str.to_string(),
+                        project: Some(project.clone()),
+                        explicit_name: explicit_name.is_some(),
+                        index,
+                        max_retries,
+                        priority,
+                        healthcheck: healthcheck.clone(),
+                        image: image.clone(),
+                        name_pattern: Self::name_pattern(
+                            &project,
+                            service_name_str,
+                            options,
+                            explicit_name.is_none(),
+                            (replicas > 1).then_some(index),
+                        ),
+                    });
+                }
+                decision.included = true;
+                parsed.decisions.push(decision);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// The override file compose would merge automatically: `<stem>.override`
+    /// with a `.yml` or `.yaml` extension, next to the base file.
+    fn find_override(file_path: &Path) -> Option<PathBuf> {
+        let stem = file_path.file_stem()?.to_str()?;
+        if stem.ends_with(".override") {
+            return None;
+        }
+        ["yml", "yaml"]
+            .iter()
+            .map(|ext| file_path.with_file_name(format!("{}.override.{}", stem, ext)))
+            .find(|path| path.is_file())
+    }
+
+    /// Finds the service definitions, by declared (or apparent) schema
+    /// version. Version 2 and 3 files, and current files without `version`,
+    /// nest them under `services`; version 1 files put them at the top level.
+    fn services(yaml: &Value, file_path: &Path) -> Option<Mapping> {
+        let version = match yaml.get("version") {
+            Some(Value::String(version)) => Some(version.clone()),
+            Some(Value::Number(version)) => Some(version.to_string()),
+            _ => None,
+        };
+
+        if let Some(services) = yaml.get("services") {
+            if let Some(version) = version.as_deref().filter(|v| v.starts_with('1')) {
+                warn!(
+                    "{} declares version {} but uses a `services` key; parsing it as a current compose file",
+                    file_path.display(),
+                    version
+                );
+            } else if let Some(version) = &version {
+                debug!(
+                    "{} declares obsolete version {}, which compose ignores",
+                    file_path.display(),
+                    version
+                );
+            }
+            return match services.as_mapping() {
+                Some(services) => Some(services.clone()),
+                None => {
+                    warn!(
+                        "{} has a `services` key that is not a mapping; no containers found",
+                        file_path.display()
+                    );
+                    None
+                }
+            };
+        }
+
+        // Version 1: every top-level mapping with an image or build is a service
+        let root = yaml.as_mapping()?;
+        let services: Mapping = root
+            .iter()
+            .filter(|(key, value)| {
+                key.as_str()
+                    .is_some_and(|key| key != "version" && !key.starts_with("x-"))
+                    && (value.get("image").is_some() || value.get("build").is_some())
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        if services.is_empty() {
+            warn!(
+                "{} has no `services` key{}; no containers found",
+                file_path.display(),
+                version
+                    .map(|v| format!(" (declared version {})", v))
+                    .unwrap_or_default()
+            );
+            return None;
+        }
+        warn!(
+            "{} has no `services` key, parsing it as a version 1 compose file; \
+             current compose tools may refuse it, consider moving services under `services`",
+            file_path.display()
+        );
+        Some(services)
+    }
+
+    /// Variables for `${VAR}` interpolation: the project `.env`, then the
+    /// service's `env_file` entries in order (later files win), with the
+    /// process environment taking precedence over all of them.
+    fn interpolation_vars(
+        service_config: &Value,
+        base_dir: &Path,
+        dotenv: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        let mut vars = dotenv.clone();
+
+        // `env_file` is a path, a list of paths, or a list of
+        // `{path, required}` entries
+        let entries = match service_config.get("env_file") {
+            Some(Value::String(path)) => vec![(path.as_str(), true)],
+            Some(Value::Sequence(list)) => list
+                .iter()
+                .filter_map(|entry| match entry {
+                    Value::String(path) => Some((path.as_str(), true)),
+                    _ => Some((
+                        entry.get("path")?.as_str()?,
+                        entry
+                            .get("required")
+                            .and_then(|r| r.as_bool())
+                            .unwrap_or(true),
+                    )),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        for (path, required) in entries {
+            let path = base_dir.join(path);
+            match load_env_file(&path) {
+                Some(file_vars) => vars.extend(file_vars),
+                None if required => warn!(
+                    "env_file {} not found; ignoring it for name interpolation",
+                    path.display()
+                ),
+                None => debug!("Optional env_file {} not found", path.display()),
+            }
+        }
+
+        vars.extend(std::env::vars());
+        vars
+    }
+
+    /// Replica count from `deploy.replicas`, or the legacy `scale` key.
+    /// Defaults to 1.
+    fn parse_replicas(service_config: &Value) -> u32 {
+        service_config
+            .get("deploy")
+            .and_then(|deploy| deploy.get("replicas"))
+            .or_else(|| service_config.get("scale"))
+            .and_then(|replicas| replicas.as_u64())
+            .map_or(1, |replicas| replicas.min(u32::MAX as u64) as u32)
+    }
+
+    /// Reads service labels in either the mapping form (`key: value`) or the
+    /// list form (`- key=value`).
+    fn parse_labels(service_config: &Value) -> HashMap<String, String> {
+        let mut labels = HashMap::new();
+
+        match service_config.get("labels") {
+            Some(Value::Mapping(map)) => {
+                for (key, value) in map {
+                    let Some(key) = key.as_str() else { continue };
+                    let value = match value {
+                        Value::String(s) => s.clone(),
+                        Value::Bool(b) => b.to_string(),
+                        Value::Number(n) => n.to_string(),
+                        _ => continue,
+                    };
+                    labels.insert(key.to_string(), value);
+                }
+            }
+            Some(Value::Sequence(list)) => {
+                for entry in list.iter().filter_map(|entry| entry.as_str()) {
+                    let (key, value) = entry.split_once('=').unwrap_or((entry, ""));
+                    labels.insert(key.to_string(), value.to_string());
+                }
+            }
+            _ => {}
+        }
+
+        labels
+    }
+
+    /// Returns `None` when the healthcheck block is explicitly disabled, either
+    /// with `disable: true` or `test: ["NONE"]`.
+    fn parse_healthcheck(healthcheck: &Value) -> Option<HealthcheckSpec> {
+        if healthcheck
+            .get("disable")
+            .and_then(|d| d.as_bool())
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        let test_is_none = match healthcheck.get("test") {
+            Some(Value::String(test)) => test == "NONE",
+            Some(Value::Sequence(test)) => test.first().and_then(|t| t.as_str()) == Some("NONE"),
+            _ => false,
+        };
+        if test_is_none {
+            return None;
+        }
+
+        let start_period = healthcheck
+            .get("start_period")
+            .and_then(|p| p.as_str())
+            .and_then(parse_duration);
+
+        Some(HealthcheckSpec { start_period })
+    }
+
+    /// The pattern for a template-named container: any replica number for
+    /// an unscaled service, exactly `index` for a scaled one.
+    fn name_pattern(
+        project: &str,
+        service_name: &str,
+        options: &ParseOptions,
+        template_named: bool,
+        index: Option<u32>,
+    ) -> Option<NamePattern> {
+        if !options.name_patterns || !template_named {
+            return None;
+        }
+        Some(NamePattern::new(
+            &options.name_template,
+            project,
+            service_name,
+            index,
+        ))
+    }
+
+    /// The compose project name: `COMPOSE_PROJECT_NAME` from the process
+    /// environment or the project `.env`, as compose itself honors it, else
+    /// the lowercased name of the directory holding the file. The path is
+    /// resolved first, so a bare `compose.yml` takes the name of the working
+    /// directory and a symlinked file that of its real location. A file at
+    /// the filesystem root has no such directory and gets
+    /// `DEFAULT_PROJECT_NAME`.
+    pub fn project_name(file_path: &Path, dotenv: &HashMap<String, String>) -> String {
+        Self::resolve_project_name(
+            file_path,
+            std::env::var("COMPOSE_PROJECT_NAME").ok(),
+            dotenv,
+        )
+    }
+
+    /// `project_name` with the environment's `COMPOSE_PROJECT_NAME` passed
+    /// in.
+    fn resolve_project_name(
+        file_path: &Path,
+        env_project: Option<String>,
+        dotenv: &HashMap<String, String>,
+    ) -> String {
+        if let Some(name) = env_project
+            .or_else(|| dotenv.get("COMPOSE_PROJECT_NAME").cloned())
+            .filter(|name| !name.trim().is_empty())
+        {
+            debug!(
+                "Using COMPOSE_PROJECT_NAME {} as the project of {}",
+                name,
+                file_path.display()
+            );
+            return name.trim().to_lowercase();
+        }
+
+        let resolved = fs::canonicalize(file_path)
+            .or_else(|_| std::path::absolute(file_path))
+            .unwrap_or_else(|_| file_path.to_path_buf());
+        match resolved.parent().and_then(|dir| dir.file_name()) {
+            Some(dir) => dir.to_string_lossy().to_lowercase(),
+            None => {
+                warn!(
+                    "{} has no parent directory to name its project after, using \"{}\"; set container_name or enable label_matching if names don't match",
+                    file_path.display(),
+                    DEFAULT_PROJECT_NAME
+                );
+                DEFAULT_PROJECT_NAME.to_string()
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Kubernetes YAML Parser
+// =============================================================================
+
+/// Enumerates containers from `podman play kube` YAML (`kind: Pod` and
+/// `kind: Deployment`). Podman names each container `<pod>-<container>`, and
+/// the pod of a Deployment `<deployment>-pod`.
+pub struct KubeParser;
+
+impl KubeParser {
+    fn is_kube_document(document: &Value) -> bool {
+        document.get("apiVersion").is_some() && document.get("kind").is_some()
+    }
+
+    fn parse_file(documents: &[Value]) -> Result<ParsedFile> {
+        let mut parsed = ParsedFile::default();
+
+        for document in documents.iter().filter(|d| Self::is_kube_document(d)) {
+            let kind = document.get("kind").and_then(|k| k.as_str()).unwrap_or("");
+            let name = document
+                .get("metadata")
+                .and_then(|m| m.get("name"))
+                .and_then(|n| n.as_str())
+                .with_context(|| format!("{} is missing metadata.name", kind))?;
+
+            let (pod_name, pod_spec) = match kind {
+                "Pod" => (name.to_string(), document.get("spec")),
+                "Deployment" => (
+                    format!("{}-pod", name),
+                    document
+                        .get("spec")
+                        .and_then(|s| s.get("template"))
+                        .and_then(|t| t.get("spec")),
+                ),
+                _ => {
+                    debug!("Skipping unsupported kind {} ({})", kind, name);
+                    continue;
+                }
+            };
+
+            let declared_policy = pod_spec
+                .and_then(|s| s.get("restartPolicy"))
+                .and_then(|p| p.as_str());
+            let specs = pod_spec
+                .and_then(|s| s.get("containers"))
+                .and_then(|c| c.as_sequence())
+                .into_iter()
+                .flatten()
+                .filter_map(|container| {
+                    let name = container.get("name").and_then(|n| n.as_str())?;
+                    let image = container.get("image").and_then(|i| i.as_str());
+                    Some((name, image))
                 });
+            let restart_policy = match declared_policy {
+                Some("Never") => {
+                    debug!("Skipping {} - restartPolicy is Never", pod_name);
+                    parsed
+                        .decisions
+                        .extend(specs.map(|(container_name, _)| ServiceDecision {
+                            service: container_name.to_string(),
+                            declared_name: None,
+                            restart_policy: "Never".to_string(),
+                            container_names: Vec::new(),
+                            included: false,
+                            reason: Some(format!("restartPolicy of {} is Never", pod_name)),
+                        }));
+                    continue;
+                }
+                Some("OnFailure") => RestartPolicy::OnFailure,
+                _ => RestartPolicy::Always,
+            };
+
+            for (container_name, image) in specs {
+                let name = format!("{}-{}", pod_name, container_name);
+                parsed.decisions.push(ServiceDecision {
+                    service: container_name.to_string(),
+                    declared_name: None,
+                    restart_policy: declared_policy.unwrap_or("Always").to_string(),
+                    container_names: vec![name.clone()],
+                    included: true,
+                    reason: None,
+                });
+                parsed.containers.push(ContainerSpec {
+                    source: SourceKind::Kube,
+                    restart_policy,
+                    name,
+                    service: container_name.to_string(),
+                    project: Some(pod_name.clone()),
+                    explicit_name: true,
+                    index: 1,
+                    max_retries: None,
+                    priority: None,
+                    healthcheck: None,
+                    image: image.map(String::from),
+                    name_pattern: None,
+                });
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Merges `overlay` into `base` the way compose applies override files:
+/// mappings merge key by key, anything else in the overlay replaces the base.
+fn merge_yaml(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
             }
         }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Splits a possibly multi-document (`---` separated) YAML stream.
+fn parse_documents(content: &str) -> Result<Vec<Value>> {
+    serde_yml::Deserializer::from_str(content)
+        .map(|document| Value::deserialize(document).map_err(Into::into))
+        .collect()
+}
+
+/// Reads a `KEY=VALUE` env file, skipping blank lines and `#` comments and
+/// stripping an `export ` prefix and matching quotes. `None` if unreadable.
+fn load_env_file(path: &Path) -> Option<HashMap<String, String>> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut vars = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = [('"', '"'), ('\'', '\'')]
+            .iter()
+            .find_map(|&(open, close)| value.strip_prefix(open)?.strip_suffix(close))
+            .unwrap_or(value);
+        vars.insert(key.trim().to_string(), value.to_string());
+    }
+    Some(vars)
+}
+
+/// Substitutes `$VAR`, `${VAR}`, `${VAR:-default}` and `${VAR-default}` the
+/// way compose does; `$$` is a literal `$`. Unset variables become empty.
+fn interpolate(input: &str, vars: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(dollar) = rest.find('$') {
+        output.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+            continue;
+        }
+
+        let (expression, after) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => {
+                    output.push('$');
+                    continue;
+                }
+            }
+        } else {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            if end == 0 {
+                output.push('$');
+                continue;
+            }
+            (&rest[..end], &rest[end..])
+        };
+        rest = after;
+
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some((default, true))),
+            None => match expression.split_once('-') {
+                Some((name, default)) => (name, Some((default, false))),
+                None => (expression, None),
+            },
+        };
+        let value = match (vars.get(name), default) {
+            (Some(value), Some((default, true))) if value.is_empty() => default.to_string(),
+            (Some(value), _) => value.clone(),
+            (None, Some((default, _))) => default.to_string(),
+            (None, None) => {
+                warn!("Variable {} is not set; substituting an empty string", name);
+                String::new()
+            }
+        };
+        output.push_str(&value);
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Parses compose-style durations such as `30s`, `1m30s`, `1h` or `500ms`.
+/// Values too large for a `Duration` are rejected rather than saturated.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let value: f64 = rest[..number_len].parse().ok()?;
+        if !value.is_finite() {
+            return None;
+        }
+        rest = &rest[number_len..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds_per_unit = match &rest[..unit_len] {
+            "us" => 0.000_001,
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+
+        let part = Duration::try_from_secs_f64(value * seconds_per_unit).ok()?;
+        total = total.checked_add(part)?;
+    }
+
+    Some(total)
+}
+
+pub fn render_name_template(template: &str, project: &str, service: &str, index: u32) -> String {
+    template
+        .replace("{project}", project)
+        .replace("{service}", service)
+        .replace("{index}", &index.to_string())
+}
+
+/// A `name_template` rendered for one service, matched loosely against the
+/// names podman reports: `-` and `_` are interchangeable, ASCII case is
+/// ignored, `{index}` matches a run of digits (any number, or only the
+/// given replica) and a leading hex hash, as docker-compose prefixes when
+/// recreating, is skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamePattern {
+    parts: Vec<PatternPart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PatternPart {
+    Literal(String),
+    /// A replica number: exactly this one, or any with `None`
+    Index(Option<u32>),
+}
+
+/// Shortest hex run taken for a docker-compose recreate hash.
+const NAME_HASH_MIN_LEN: usize = 6;
+
+impl NamePattern {
+    pub fn new(template: &str, project: &str, service: &str, index: Option<u32>) -> Self {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(ch) = rest.chars().next() {
+            if let Some(after) = rest.strip_prefix("{index}") {
+                if !literal.is_empty() {
+                    parts.push(PatternPart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(PatternPart::Index(index));
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{project}") {
+                literal.push_str(project);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{service}") {
+                literal.push_str(service);
+                rest = after;
+            } else {
+                literal.push(ch);
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(PatternPart::Literal(literal));
+        }
+        Self { parts }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        if Self::match_parts(&self.parts, name) {
+            return true;
+        }
+        match name.split_once(['_', '-']) {
+            Some((hash, rest))
+                if hash.len() >= NAME_HASH_MIN_LEN
+                    && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                Self::match_parts(&self.parts, rest)
+            }
+            _ => false,
+        }
+    }
+
+    fn match_parts(parts: &[PatternPart], name: &str) -> bool {
+        let Some((part, remaining)) = parts.split_first() else {
+            return name.is_empty();
+        };
+        match part {
+            PatternPart::Literal(literal) => strip_name_prefix(name, literal)
+                .is_some_and(|rest| Self::match_parts(remaining, rest)),
+            PatternPart::Index(expected) => {
+                let digits =
+                    name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                (1..=digits).any(|len| {
+                    let (number, rest) = name.split_at(len);
+                    expected.is_none_or(|expected| number.parse() == Ok(expected))
+                        && Self::match_parts(remaining, rest)
+                })
+            }
+        }
+    }
+}
+
+/// `name` without a leading `prefix`, comparing separators and ASCII case
+/// loosely.
+fn strip_name_prefix<'a>(name: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = name.get(..prefix.len())?;
+    let same = head.chars().zip(prefix.chars()).all(|(a, b)| {
+        a.eq_ignore_ascii_case(&b) || (matches!(a, '-' | '_') && matches!(b, '-' | '_'))
+    });
+    same.then(|| &name[prefix.len()..])
+}
+
+fn is_truthy(value: &str) -> bool {
+    !matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "false" | "0" | "no" | "off"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `content` as `compose.yml` in a fresh directory named
+    /// `project` under the system temp dir, returning the file path.
+    fn write_compose(project: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("rusty-podmon-test-{}", std::process::id()))
+            .join(project);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("compose.yml");
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn options(name_template: &str) -> ParseOptions {
+        ParseOptions {
+            name_template: name_template.to_string(),
+            merge_overrides: false,
+            name_patterns: true,
+            scale: BTreeMap::new(),
+        }
+    }
+
+    const WEB_SERVICE: &str = "services:\n  web:\n    image: nginx\n    restart: always\n";
+
+    #[test]
+    fn docker_compose_names_use_underscores() {
+        let path = write_compose("dockerapp", WEB_SERVICE);
+        let containers =
+            ComposeParser::parse_containers(&path, &options("{project}_{service}_{index}"))
+                .unwrap();
+
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].name, "dockerapp_web_1");
+        let pattern = containers[0].name_pattern.as_ref().unwrap();
+        assert!(pattern.matches("dockerapp_web_1"));
+        assert!(pattern.matches("dockerapp_web_2"));
+        assert!(pattern.matches("3f2a9c1d_dockerapp_web_1"));
+        assert!(!pattern.matches("dockerapp_api_1"));
+    }
+
+    #[test]
+    fn podman_compose_names_use_hyphens() {
+        let path = write_compose("podapp", WEB_SERVICE);
+        let containers =
+            ComposeParser::parse_containers(&path, &options("{project}-{service}-{index}"))
+                .unwrap();
+
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].name, "podapp-web-1");
+        let pattern = containers[0].name_pattern.as_ref().unwrap();
+        assert!(pattern.matches("podapp-web-1"));
+        assert!(!pattern.matches("podapp-web"));
+        assert!(!pattern.matches("otherapp-web-1"));
+    }
+
+    #[test]
+    fn name_pattern_tolerates_the_other_convention() {
+        let pattern = NamePattern::new("{project}_{service}_{index}", "myapp", "web", None);
+        assert!(pattern.matches("myapp-web-1"));
+        assert!(pattern.matches("MyApp_Web_1"));
+
+        let pattern = NamePattern::new("{project}-{service}-{index}", "myapp", "web", Some(2));
+        assert!(pattern.matches("myapp_web_2"));
+        assert!(!pattern.matches("myapp-web-1"));
+    }
+
+    #[test]
+    fn project_name_of_root_level_file_is_default() {
+        let name = ComposeParser::resolve_project_name(
+            Path::new("/docker-compose.yml"),
+            None,
+            &HashMap::new(),
+        );
+        assert_eq!(name, DEFAULT_PROJECT_NAME);
+    }
+
+    #[test]
+    fn project_name_of_bare_filename_is_working_directory() {
+        let cwd = std::env::current_dir().unwrap();
+        let expected = cwd.file_name().unwrap().to_string_lossy().to_lowercase();
+        let name =
+            ComposeParser::resolve_project_name(Path::new("compose.yml"), None, &HashMap::new());
+        assert_eq!(name, expected);
+    }
+
+    #[test]
+    fn project_name_is_lowercased_parent_directory() {
+        let name = ComposeParser::resolve_project_name(
+            Path::new("/srv/MyApp/compose.yml"),
+            None,
+            &HashMap::new(),
+        );
+        assert_eq!(name, "myapp");
+    }
+
+    #[test]
+    fn compose_project_name_overrides_directory() {
+        let path = Path::new("/srv/myapp/compose.yml");
+        let dotenv =
+            HashMap::from([("COMPOSE_PROJECT_NAME".to_string(), "FromDotenv".to_string())]);
+
+        assert_eq!(
+            ComposeParser::resolve_project_name(path, None, &dotenv),
+            "fromdotenv"
+        );
+        // The process environment wins over .env, as in compose
+        assert_eq!(
+            ComposeParser::resolve_project_name(path, Some("fromenv".to_string()), &dotenv),
+            "fromenv"
+        );
+        // Blank values are ignored
+        assert_eq!(
+            ComposeParser::resolve_project_name(path, Some("  ".to_string()), &HashMap::new()),
+            "myapp"
+        );
+    }
+
+    #[test]
+    fn render_name_template_fills_placeholders() {
+        assert_eq!(
+            render_name_template("{project}_{service}_{index}", "app", "db", 1),
+            "app_db_1"
+        );
+        assert_eq!(
+            render_name_template("{project}-{service}-{index}", "app", "db", 3),
+            "app-db-3"
+        );
+    }
 
-        Ok(containers)
+    #[test]
+    fn parse_duration_accepts_compose_units() {
+        assert_eq!(parse_duration("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1.5h"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration(""), None);
     }
 
-    fn generate_default_name(file_path: &Path, service_name: &str) -> Option<String> {
-        let dir_name = file_path.parent()?.file_name()?.to_str()?.to_lowercase();
-        Some(format!("{}_{}_1", dir_name, service_name))
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        assert_eq!(parse_duration("100000000000000000000000s"), None);
+        assert_eq!(parse_duration("18446744073709551615s1s"), None);
+        assert_eq!(parse_duration(&format!("{}s", "9".repeat(400))), None);
     }
 }
@@ -1,18 +1,218 @@
+use crate::cli_config::RestartHook;
+use crate::runner::{CommandRunner, Invocation, SystemRunner, run_logged};
+
 use anyhow::{Context, Result};
-use std::collections::HashSet;
-use std::path::Path;
-use std::process::Command;
-use tracing::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{debug, warn};
 
 // =============================================================================
 // External Command Interface
 // =============================================================================
 
-pub struct PodmanClient;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContainerExit {
+    pub exit_code: i32,
+    pub oom_killed: bool,
+}
+
+/// A container's healthcheck state as reported by `podman inspect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Healthy,
+    Unhealthy,
+    /// Within the healthcheck's start period or awaiting its first probe
+    Starting,
+    /// The container has no healthcheck
+    None,
+}
+
+/// A container as reported by `podman ps -a`, with its compose identity
+/// labels and pod when present.
+#[derive(Debug, Clone)]
+pub struct LabeledContainer {
+    pub name: String,
+    pub project: Option<String>,
+    pub service: Option<String>,
+    pub pod: Option<String>,
+}
+
+const PROJECT_LABELS: [&str; 2] = ["com.docker.compose.project", "io.podman.compose.project"];
+const SERVICE_LABELS: [&str; 2] = ["com.docker.compose.service", "io.podman.compose.service"];
+
+/// The compose implementation that drives compose stacks. The two accept the
+/// same subcommands but are invoked differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ComposeTool {
+    /// The standalone Python `podman-compose`
+    #[serde(rename = "podman-compose")]
+    Standalone,
+    /// The `podman compose` subcommand built into newer podman
+    #[serde(rename = "podman compose")]
+    Builtin,
+}
+
+impl ComposeTool {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ComposeTool::Standalone => "podman-compose",
+            ComposeTool::Builtin => "podman compose",
+        }
+    }
+
+    /// Prefers the built-in `podman compose`, falling back to the standalone
+    /// tool. Returns `None` when neither responds.
+    pub fn detect(runner: &Arc<dyn CommandRunner>) -> Option<Self> {
+        let works = |program: &str, args: &[&str]| {
+            Invocation::new(program, Arc::clone(runner))
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+
+        if works("podman", &["compose", "version"]) {
+            Some(ComposeTool::Builtin)
+        } else if works("podman-compose", &["--version"]) {
+            Some(ComposeTool::Standalone)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extra `up` flags for compose restarts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpOptions {
+    /// Recreate containers even when their definition looks unchanged
+    pub force_recreate: bool,
+    /// Pull images before recreating
+    pub pull_always: bool,
+    /// Replica count per service, passed as `--scale service=N`
+    pub scale: BTreeMap<String, u32>,
+}
+
+/// Upper bound on the bytes of container names passed to one command, well
+/// below the smallest common `ARG_MAX` so the environment still fits.
+const MAX_ARG_BYTES: usize = 64 * 1024;
+
+/// Client certificates for mutual TLS with a `tcp://` podman service,
+/// exported as `CONTAINER_TLS_CA`, `CONTAINER_TLS_CERT` and
+/// `CONTAINER_TLS_KEY`. Only the podman CLI acts on them, from 5.6 on;
+/// older versions ignore them and connect in plaintext.
+#[derive(Debug, Clone, Default)]
+pub struct RemoteTls {
+    pub ca: Option<PathBuf>,
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+impl RemoteTls {
+    /// First podman release that reads `CONTAINER_TLS_*`
+    pub const MIN_PODMAN: (u32, u32) = (5, 6);
+
+    pub fn is_empty(&self) -> bool {
+        self.ca.is_none() && self.cert.is_none() && self.key.is_none()
+    }
+
+    fn env(&self) -> impl Iterator<Item = (&'static str, &Path)> {
+        [
+            ("CONTAINER_TLS_CA", &self.ca),
+            ("CONTAINER_TLS_CERT", &self.cert),
+            ("CONTAINER_TLS_KEY", &self.key),
+        ]
+        .into_iter()
+        .filter_map(|(key, path)| Some((key, path.as_deref()?)))
+    }
+}
+
+/// Runs podman and podman-compose. When a connection URL is configured it is
+/// exported as `CONTAINER_HOST`, which both tools honor, so every command
+/// talks to the same (possibly rootless or remote) podman service.
+pub struct PodmanClient {
+    container_host: Option<String>,
+    tls: RemoteTls,
+    compose_tool: ComposeTool,
+    runner: Arc<dyn CommandRunner>,
+}
 
 impl PodmanClient {
-    pub fn get_running_containers() -> Result<HashSet<String>> {
-        let output = Command::new("podman")
+    pub fn new(container_host: Option<String>, compose_tool: ComposeTool) -> Self {
+        Self::with_runner(container_host, compose_tool, Arc::new(SystemRunner))
+    }
+
+    /// Like `new`, but executes every command through `runner`.
+    pub fn with_runner(
+        container_host: Option<String>,
+        compose_tool: ComposeTool,
+        runner: Arc<dyn CommandRunner>,
+    ) -> Self {
+        Self {
+            container_host,
+            tls: RemoteTls::default(),
+            compose_tool,
+            runner,
+        }
+    }
+
+    /// The local podman CLI's `(major, minor)` version, from
+    /// `podman --version` ("podman version 5.6.1").
+    pub fn version(&self) -> Result<(u32, u32)> {
+        let output = self
+            .podman()
+            .arg("--version")
+            .output()
+            .context("Failed to execute 'podman --version'")?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_version(&stdout)
+            .with_context(|| format!("Unrecognized podman version: {}", stdout.trim()))
+    }
+
+    /// Authenticates to a `tcp://` container host with `tls`.
+    pub fn with_tls(mut self, tls: RemoteTls) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn tls(&self) -> &RemoteTls {
+        &self.tls
+    }
+
+    fn podman(&self) -> Invocation {
+        self.command("podman")
+    }
+
+    fn compose(&self) -> Invocation {
+        match self.compose_tool {
+            ComposeTool::Standalone => self.command("podman-compose"),
+            ComposeTool::Builtin => {
+                let mut command = self.command("podman");
+                command.arg("compose");
+                command
+            }
+        }
+    }
+
+    fn command(&self, program: &str) -> Invocation {
+        let mut command = Invocation::new(program, Arc::clone(&self.runner));
+        if let Some(host) = &self.container_host {
+            command.env("CONTAINER_HOST", host);
+            for (key, path) in self.tls.env() {
+                command.env(key, &path.display().to_string());
+            }
+        }
+        command
+    }
+
+    pub fn get_running_containers(&self) -> Result<HashSet<String>> {
+        let output = self
+            .podman()
             .args(["ps", "--format", "{{.Names}}"])
             .output()
             .context("Failed to execute 'podman ps'")?;
@@ -22,47 +222,737 @@ impl PodmanClient {
             return Err(anyhow::anyhow!("podman ps failed: {}", stderr));
         }
 
-        let stdout =
-            String::from_utf8(output.stdout).context("Invalid UTF-8 in podman command output")?;
+        // Decoded per name, so one undecodable name doesn't hide the rest;
+        // it could never equal a managed (UTF-8) name anyway
+        Ok(output
+            .stdout
+            .split(|byte| *byte == b'\n')
+            .filter_map(|line| match std::str::from_utf8(line) {
+                Ok(name) => Some(name.trim()),
+                Err(_) => {
+                    warn!(
+                        "Ignoring container with a name that is not valid UTF-8: {}",
+                        String::from_utf8_lossy(line).trim()
+                    );
+                    None
+                }
+            })
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    /// Pulls `image` and returns the ID of the local image it now names.
+    pub fn pull_image(&self, image: &str) -> Result<String> {
+        let output = self
+            .podman()
+            .args(["pull", "--quiet", image])
+            .output()
+            .context("Failed to execute 'podman pull'")?;
 
-        Ok(stdout
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!(
+                "podman pull {} failed: {}",
+                image,
+                stderr.trim()
+            ));
+        }
+
+        // Progress may precede it; the ID is the last line
+        String::from_utf8_lossy(&output.stdout)
             .lines()
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| line.trim().to_string())
+            .map(str::trim)
+            .rfind(|line| !line.is_empty())
+            .map(String::from)
+            .with_context(|| format!("podman pull {} printed no image ID", image))
+    }
+
+    /// ID of the image a container was created from.
+    pub fn container_image_id(&self, container_name: &str) -> Result<String> {
+        let output = self
+            .podman()
+            .args(["inspect", "--format", "{{.Image}}", container_name])
+            .output()
+            .context("Failed to execute 'podman inspect'")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!(
+                "podman inspect {} failed: {}",
+                container_name,
+                stderr.trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// When a container was last started (`State.StartedAt`).
+    pub fn get_start_time(&self, container_name: &str) -> Result<OffsetDateTime> {
+        let output = self
+            .podman()
+            .args([
+                "inspect",
+                "--format",
+                "{{.State.StartedAt}}",
+                container_name,
+            ])
+            .output()
+            .context("Failed to execute 'podman inspect'")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!(
+                "podman inspect {} failed: {}",
+                container_name,
+                stderr.trim()
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_start_time(stdout.trim()).with_context(|| {
+            format!(
+                "Unexpected start time for {}: {}",
+                container_name,
+                stdout.trim()
+            )
+        })
+    }
+
+    /// `podman events` streaming container deaths as JSON lines, for
+    /// `EventStream`.
+    pub fn die_events(&self) -> Invocation {
+        let mut command = self.podman();
+        command.args([
+            "events",
+            "--format",
+            "json",
+            "--filter",
+            "type=container",
+            "--filter",
+            "event=died",
+        ]);
+        command
+    }
+
+    /// Lists all containers (running or not) together with their compose
+    /// project/service labels and the pod they belong to.
+    pub fn get_containers_with_labels(&self) -> Result<Vec<LabeledContainer>> {
+        let output = self
+            .podman()
+            .args(["ps", "-a", "--format", "json"])
+            .output()
+            .context("Failed to execute 'podman ps -a'")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("podman ps -a failed: {}", stderr));
+        }
+
+        let containers = parse_json(&String::from_utf8_lossy(&output.stdout))?;
+        let containers = containers
+            .as_array()
+            .context("Expected a JSON array from 'podman ps -a'")?;
+
+        Ok(containers
+            .iter()
+            .filter_map(|container| {
+                let name = match container.get("Names")? {
+                    Value::Array(names) => names.first()?.as_str()?,
+                    Value::String(name) => name.as_str(),
+                    _ => return None,
+                };
+                let labels = container.get("Labels");
+                let label = |keys: &[&str]| {
+                    keys.iter()
+                        .find_map(|key| labels?.get(*key)?.as_str())
+                        .map(String::from)
+                };
+
+                let pod = container
+                    .get("PodName")
+                    .and_then(|pod| pod.as_str())
+                    .filter(|pod| !pod.is_empty())
+                    .map(String::from);
+
+                Some(LabeledContainer {
+                    name: name.to_string(),
+                    project: label(&PROJECT_LABELS),
+                    service: label(&SERVICE_LABELS),
+                    pod,
+                })
+            })
             .collect())
     }
 
-    pub fn restart_compose_service(compose_file: &Path) -> Result<()> {
-        let compose_dir = compose_file
-            .parent()
-            .context("Failed to get parent directory of compose file")?;
+    /// Runs the container's configured healthcheck once. Returns `Ok(false)`
+    /// when the probe ran and reported unhealthy, and an error when the probe
+    /// could not be run at all (e.g. no healthcheck defined).
+    pub fn run_healthcheck(&self, container_name: &str) -> Result<bool> {
+        let output = self
+            .podman()
+            .args(["healthcheck", "run", container_name])
+            .output()
+            .context("Failed to execute 'podman healthcheck run'")?;
+
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(anyhow::anyhow!(
+                    "podman healthcheck run {} failed: {}",
+                    container_name,
+                    stderr.trim()
+                ))
+            }
+        }
+    }
+
+    /// Reads the status podman's own healthcheck runs last recorded, without
+    /// running a probe.
+    pub fn health_status(&self, container_name: &str) -> Result<HealthStatus> {
+        let output = self
+            .podman()
+            .args([
+                "inspect",
+                "--type",
+                "container",
+                "--format",
+                "{{.State.Health.Status}}",
+                container_name,
+            ])
+            .output()
+            .context("Failed to execute 'podman inspect'")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("podman inspect failed: {}", stderr));
+        }
+
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "healthy" => Ok(HealthStatus::Healthy),
+            "unhealthy" => Ok(HealthStatus::Unhealthy),
+            "starting" => Ok(HealthStatus::Starting),
+            "" | "<no value>" => Ok(HealthStatus::None),
+            other => Err(anyhow::anyhow!("Unknown health status: {}", other)),
+        }
+    }
+
+    /// Returns the exit status of each named container that exists, using one
+    /// `podman inspect` per chunk of names rather than one per container.
+    /// Containers that were never created or have been removed are omitted.
+    pub fn inspect_many(&self, names: &[String]) -> Result<HashMap<String, ContainerExit>> {
+        let mut exits = HashMap::new();
+
+        for chunk in chunk_args(names, MAX_ARG_BYTES) {
+            let output = self
+                .podman()
+                .args(["inspect", "--type", "container", "--format", "json"])
+                .args(chunk)
+                .output()
+                .context("Failed to execute 'podman inspect'")?;
+
+            // podman still prints the containers it found when some names are
+            // missing, and exits non-zero
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if stdout.trim().is_empty() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if output.status.success() || stderr.contains("no such") {
+                    continue;
+                }
+                return Err(anyhow::anyhow!("podman inspect failed: {}", stderr));
+            }
+
+            let containers = parse_json(&stdout)?;
+            let containers = containers
+                .as_array()
+                .context("Expected a JSON array from 'podman inspect'")?;
+
+            for container in containers {
+                let Some(name) = container.get("Name").and_then(|n| n.as_str()) else {
+                    continue;
+                };
+                let state = container.get("State");
+                let Some(exit_code) = state
+                    .and_then(|s| s.get("ExitCode"))
+                    .and_then(|c| c.as_i64())
+                else {
+                    continue;
+                };
+                let oom_killed = state
+                    .and_then(|s| s.get("OOMKilled"))
+                    .and_then(|o| o.as_bool())
+                    .unwrap_or(false);
+
+                exits.insert(
+                    name.trim_start_matches('/').to_string(),
+                    ContainerExit {
+                        exit_code: exit_code as i32,
+                        oom_killed,
+                    },
+                );
+            }
+        }
+
+        Ok(exits)
+    }
+
+    /// Returns the last `lines` lines a container wrote to stdout and stderr.
+    pub fn get_logs(&self, container_name: &str, lines: usize) -> Result<String> {
+        let output = self
+            .podman()
+            .args(["logs", "--tail", &lines.to_string(), container_name])
+            .output()
+            .context("Failed to execute 'podman logs'")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("podman logs failed: {}", stderr));
+        }
+
+        // podman logs replays the container's stderr on its own stderr
+        let mut logs = String::from_utf8_lossy(&output.stdout).into_owned();
+        logs.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(logs)
+    }
+
+    /// Runs a container's `custom_restart_command` through `sh -c`, with
+    /// `PODMON_CONTAINER` and `PODMON_COMPOSE_FILE` set (and `CONTAINER_HOST`,
+    /// like every podman call). Exit code 0 is success; a command still
+    /// running after `timeout` is killed and counts as failed.
+    pub async fn run_custom_restart(
+        &self,
+        command: &str,
+        container_name: &str,
+        compose_file: &Path,
+        timeout: Duration,
+    ) -> Result<()> {
+        debug!("Running custom restart for {}: {}", container_name, command);
+
+        let mut invocation = self.command("sh");
+        invocation
+            .args(["-c", command])
+            .env("PODMON_CONTAINER", container_name)
+            .env("PODMON_COMPOSE_FILE", &compose_file.display().to_string());
+        run_logged(
+            &invocation,
+            "Custom restart",
+            "restart",
+            container_name,
+            timeout,
+        )
+        .await
+    }
+
+    /// Runs a container's `pre_restart_command` or `post_restart_command`
+    /// like `run_custom_restart`, with `PODMON_PHASE` set to `pre` or `post`.
+    pub async fn run_restart_hook(
+        &self,
+        hook: RestartHook,
+        command: &str,
+        container_name: &str,
+        compose_file: &Path,
+        timeout: Duration,
+    ) -> Result<()> {
+        debug!("Running {} for {}: {}", hook, container_name, command);
+
+        let mut invocation = self.command("sh");
+        invocation
+            .args(["-c", command])
+            .env("PODMON_CONTAINER", container_name)
+            .env("PODMON_COMPOSE_FILE", &compose_file.display().to_string())
+            .env("PODMON_PHASE", hook.as_str());
+        let tag = format!("{}-restart", hook.as_str());
+        run_logged(
+            &invocation,
+            &hook.to_string(),
+            &tag,
+            container_name,
+            timeout,
+        )
+        .await
+    }
+
+    /// Whether `command` exits 0 inside the container (`podman exec`). A
+    /// command still running after `timeout` is killed and counts as failed.
+    pub async fn exec_succeeds(
+        &self,
+        container_name: &str,
+        command: &[String],
+        timeout: Duration,
+    ) -> Result<bool> {
+        let mut invocation = self.podman();
+        invocation.arg("exec").arg(container_name).args(command);
+        match invocation.output_with_timeout(timeout).await {
+            Ok(output) => Ok(output.status.success()),
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => Ok(false),
+            Err(e) => Err(e).context("Failed to execute 'podman exec'"),
+        }
+    }
+
+    /// Brings back one existing container without recreating it: `podman
+    /// restart` if it is running (e.g. unhealthy), `podman start` if stopped.
+    /// `stop_timeout` is the grace period before a restarted container is
+    /// killed.
+    pub fn restart_container(
+        &self,
+        container_name: &str,
+        running: bool,
+        stop_timeout: Option<u64>,
+    ) -> Result<()> {
+        let subcommand = if running { "restart" } else { "start" };
+        debug!("Running podman {} {}", subcommand, container_name);
+
+        let mut command = self.podman();
+        command.arg(subcommand);
+        if let Some(seconds) = stop_timeout.filter(|_| running) {
+            command.args(["-t", &seconds.to_string()]);
+        }
+        let output = command
+            .arg(container_name)
+            .output()
+            .with_context(|| format!("Failed to execute 'podman {}'", subcommand))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!(
+                "podman {} {} failed: {}",
+                subcommand,
+                container_name,
+                stderr
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Restarts every container of a pod, including its infra container.
+    pub fn restart_pod(&self, pod_name: &str) -> Result<()> {
+        debug!("Restarting pod {}", pod_name);
+
+        let output = self
+            .podman()
+            .args(["pod", "restart", pod_name])
+            .output()
+            .context("Failed to execute 'podman pod restart'")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("podman pod restart failed: {}", stderr));
+        }
+
+        Ok(())
+    }
+
+    /// Recreates the pods defined in a Kubernetes YAML file.
+    pub fn replay_kube(&self, kube_file: &Path) -> Result<()> {
+        debug!("Replaying kube file {}", kube_file.display());
+
+        let output = self
+            .podman()
+            .args(["play", "kube", "--replace"])
+            .arg(kube_file)
+            .output()
+            .context("Failed to execute 'podman play kube'")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("podman play kube failed: {}", stderr));
+        }
+
+        Ok(())
+    }
+
+    /// Runs `down` then `up -d` for the stack. `extra_args` (e.g. `--profile
+    /// prod`) are passed before the subcommand on both invocations, where
+    /// compose expects its global options; `up` options go after it.
+    /// `stop_timeout` is the grace period `down` gives containers before
+    /// killing them.
+    pub fn restart_compose_service(
+        &self,
+        compose_file: &Path,
+        working_dir: Option<&Path>,
+        extra_args: &[String],
+        stop_timeout: Option<u64>,
+        up: UpOptions,
+    ) -> Result<()> {
+        // From another directory, the file has to be named explicitly (and
+        // absolutely, as a relative path would resolve against that directory)
+        let (compose_dir, file_args) = match working_dir {
+            Some(dir) => {
+                let file = std::path::absolute(compose_file).with_context(|| {
+                    format!("Failed to resolve compose file {}", compose_file.display())
+                })?;
+                (dir, vec![OsString::from("-f"), file.into_os_string()])
+            }
+            None => (
+                compose_file
+                    .parent()
+                    .context("Failed to get parent directory of compose file")?,
+                Vec::new(),
+            ),
+        };
 
         debug!("Restarting compose services in {}", compose_dir.display());
 
         // Stop services
-        let output = Command::new("podman-compose")
+        let mut command = self.compose();
+        command
             .current_dir(compose_dir)
-            .args(["down"])
+            .args(&file_args)
+            .args(extra_args)
+            .arg("down");
+        if let Some(seconds) = stop_timeout {
+            command.args(["--timeout", &seconds.to_string()]);
+        }
+        let output = command
             .output()
-            .context("Failed to execute 'podman-compose down'")?;
+            .with_context(|| format!("Failed to execute '{} down'", self.compose_tool.as_str()))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("podman-compose down failed: {}", stderr));
+            return Err(anyhow::anyhow!(
+                "{} down failed: {}",
+                self.compose_tool.as_str(),
+                stderr
+            ));
         }
 
         // Start services
-        let output = Command::new("podman-compose")
+        let mut command = self.compose();
+        command
             .current_dir(compose_dir)
-            .args(["up", "-d"])
+            .args(&file_args)
+            .args(extra_args)
+            .args(["up", "-d"]);
+        if up.force_recreate {
+            command.arg("--force-recreate");
+        }
+        for (service, replicas) in &up.scale {
+            command.args(["--scale", &format!("{}={}", service, replicas)]);
+        }
+        if up.pull_always {
+            // podman-compose spells it as one flag, docker compose (behind
+            // `podman compose`) takes a pull policy
+            match self.compose_tool {
+                ComposeTool::Standalone => command.arg("--pull-always"),
+                ComposeTool::Builtin => command.args(["--pull", "always"]),
+            };
+        }
+        let output = command
             .output()
-            .context("Failed to execute 'podman-compose up'")?;
+            .with_context(|| format!("Failed to execute '{} up'", self.compose_tool.as_str()))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow::anyhow!("podman-compose up failed: {}", stderr));
+            return Err(anyhow::anyhow!(
+                "{} up failed: {}",
+                self.compose_tool.as_str(),
+                stderr
+            ));
         }
 
         Ok(())
     }
 }
+
+/// Splits arguments into chunks whose combined length (counting a separator
+/// per argument) stays within `max_bytes`. An oversized single argument still
+/// gets a chunk of its own.
+fn chunk_args(args: &[String], max_bytes: usize) -> Vec<&[String]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut size = 0;
+
+    for (i, arg) in args.iter().enumerate() {
+        let len = arg.len() + 1;
+        if i > start && size + len > max_bytes {
+            chunks.push(&args[start..i]);
+            start = i;
+            size = 0;
+        }
+        size += len;
+    }
+    if start < args.len() {
+        chunks.push(&args[start..]);
+    }
+
+    chunks
+}
+
+/// Parses podman's JSON output.
+pub fn parse_json(text: &str) -> Result<Value> {
+    serde_json::from_str(text).context("Invalid JSON in podman command output")
+}
+
+/// Looks for podman API sockets of both rootful (`/run/podman`) and rootless
+/// (`/run/user/<uid>/podman`) services, used to hint at a user mismatch.
+pub fn find_podman_sockets() -> Vec<PathBuf> {
+    let mut sockets = Vec::new();
+
+    let rootful = PathBuf::from("/run/podman/podman.sock");
+    if rootful.exists() {
+        sockets.push(rootful);
+    }
+
+    if let Ok(entries) = fs::read_dir("/run/user") {
+        for entry in entries.flatten() {
+            let socket = entry.path().join("podman/podman.sock");
+            if socket.exists() {
+                sockets.push(socket);
+            }
+        }
+    }
+
+    sockets.sort();
+    sockets
+}
+
+/// Resolves a user name or numeric uid to that user's rootless podman socket.
+pub fn rootless_socket_url(user: &str) -> Result<String> {
+    let uid = match user.parse::<u32>() {
+        Ok(uid) => uid,
+        Err(_) => {
+            let passwd = fs::read_to_string("/etc/passwd").context("Failed to read /etc/passwd")?;
+            passwd
+                .lines()
+                .find_map(|line| {
+                    let mut fields = line.split(':');
+                    (fields.next()? == user).then_some(())?;
+                    fields.nth(1)?.parse().ok()
+                })
+                .with_context(|| format!("Unknown podman_user: {}", user))?
+        }
+    };
+
+    Ok(format!("unix:///run/user/{}/podman/podman.sock", uid))
+}
+
+/// Parses podman's Go-formatted timestamps, e.g.
+/// `2024-05-01 12:00:03.123456789 +0000 UTC` (the zone name is ignored; Go
+/// leaves out the fraction when it is zero).
+fn parse_start_time(value: &str) -> Result<OffsetDateTime> {
+    let mut fields = value.split_whitespace();
+    let (Some(date), Some(clock), Some(offset)) = (fields.next(), fields.next(), fields.next())
+    else {
+        anyhow::bail!("expected date, time and offset");
+    };
+    let fraction = if clock.contains('.') { "" } else { ".0" };
+    let timestamp = format!("{} {}{} {}", date, clock, fraction, offset);
+    let format = time::format_description::parse_borrowed::<2>(
+        "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond] \
+         [offset_hour sign:mandatory][offset_minute]",
+    )?;
+    Ok(OffsetDateTime::parse(&timestamp, &format)?)
+}
+
+/// `(major, minor)` from the last word of a `--version` line.
+fn parse_version(line: &str) -> Option<(u32, u32)> {
+    let version = line.split_whitespace().last()?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{CommandOutput, MockRunner};
+
+    fn client(runner: MockRunner) -> PodmanClient {
+        PodmanClient::with_runner(None, ComposeTool::Standalone, Arc::new(runner))
+    }
+
+    #[test]
+    fn running_containers_skip_names_that_are_not_utf8() {
+        let podman = client(MockRunner::new(|_| {
+            Ok(CommandOutput::success(
+                b"web\nbad\xff\xfename\n  db  \n\n\xc3\x28\napi\n".to_vec(),
+            ))
+        }));
+
+        let running = podman.get_running_containers().unwrap();
+
+        let expected: HashSet<String> = ["web", "db", "api"].map(String::from).into();
+        assert_eq!(running, expected);
+    }
+
+    #[test]
+    fn running_containers_fail_when_podman_ps_fails() {
+        let podman = client(MockRunner::new(|_| {
+            Ok(CommandOutput::failure(125, "cannot connect"))
+        }));
+
+        let error = podman.get_running_containers().unwrap_err();
+        assert!(format!("{:#}", error).contains("cannot connect"));
+    }
+
+    #[tokio::test]
+    async fn exec_probe_follows_the_exit_status() {
+        let podman = client(MockRunner::new(|invocation| {
+            Ok(match invocation.arg_strings().last().map(String::as_str) {
+                Some("ready") => CommandOutput::success(""),
+                _ => CommandOutput::failure(1, ""),
+            })
+        }));
+        let probe = |word: &str| vec!["check".to_string(), word.to_string()];
+
+        let timeout = Duration::from_secs(1);
+        assert!(
+            podman
+                .exec_succeeds("web", &probe("ready"), timeout)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !podman
+                .exec_succeeds("web", &probe("down"), timeout)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn podman_version_is_read_from_the_last_word() {
+        assert_eq!(parse_version("podman version 5.6.1\n"), Some((5, 6)));
+        assert_eq!(parse_version("podman version 4.9.3-dev"), Some((4, 9)));
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn remote_tls_is_exported_with_the_container_host() {
+        let env = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let runner = MockRunner::new({
+            let env = Arc::clone(&env);
+            move |invocation| {
+                *env.lock().unwrap() = invocation.env.clone();
+                Ok(CommandOutput::success(""))
+            }
+        });
+        let tls = RemoteTls {
+            ca: Some(PathBuf::from("/tls/ca.pem")),
+            cert: Some(PathBuf::from("/tls/cert.pem")),
+            key: Some(PathBuf::from("/tls/key.pem")),
+        };
+        let podman = PodmanClient::with_runner(
+            Some("tcp://podman.example.com:8443".to_string()),
+            ComposeTool::Standalone,
+            Arc::new(runner),
+        )
+        .with_tls(tls);
+
+        podman.get_running_containers().unwrap();
+
+        let expected = [
+            ("CONTAINER_HOST", "tcp://podman.example.com:8443"),
+            ("CONTAINER_TLS_CA", "/tls/ca.pem"),
+            ("CONTAINER_TLS_CERT", "/tls/cert.pem"),
+            ("CONTAINER_TLS_KEY", "/tls/key.pem"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(*env.lock().unwrap(), expected);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Instant, sleep_until};
+use tracing::info;
+
+// =============================================================================
+// Restart Queue
+// =============================================================================
+
+/// A stack restart waiting for its turn under `restart_spacing_seconds`.
+pub struct RestartJob {
+    pub compose_file: PathBuf,
+    pub container_names: Vec<String>,
+    /// Time to leave after the previous restart finished
+    spacing: Duration,
+}
+
+/// A job whose turn has come. The worker holds the next one back until
+/// `done` is sent or dropped, i.e. until this restart has finished.
+pub struct ReadyRestart {
+    pub job: RestartJob,
+    pub done: oneshot::Sender<()>,
+}
+
+/// Serializes restarts with a minimum spacing between them. The monitor
+/// enqueues stacks and carries on with its loop; a worker task hands each
+/// job back through the receiver returned by `spawn` once the previous
+/// restart finished `spacing` ago, so the monitor never sleeps through the
+/// spacing itself.
+pub struct RestartQueue {
+    tx: mpsc::UnboundedSender<RestartJob>,
+    /// Containers of jobs not handed back yet
+    queued: HashSet<String>,
+}
+
+impl RestartQueue {
+    pub fn spawn() -> (Self, mpsc::UnboundedReceiver<ReadyRestart>) {
+        let (tx, jobs) = mpsc::unbounded_channel();
+        let (ready_tx, ready_rx) = mpsc::unbounded_channel();
+        tokio::spawn(pace(jobs, ready_tx));
+        let queue = Self {
+            tx,
+            queued: HashSet::new(),
+        };
+        (queue, ready_rx)
+    }
+
+    /// Queues a restart of `container_names`, to start no sooner than
+    /// `spacing` after the previous restart finished.
+    pub fn push(&mut self, compose_file: PathBuf, container_names: Vec<String>, spacing: Duration) {
+        self.queued.extend(container_names.iter().cloned());
+        let job = RestartJob {
+            compose_file,
+            container_names,
+            spacing,
+        };
+        // The worker only stops once this queue is dropped
+        let _ = self.tx.send(job);
+    }
+
+    /// Whether a restart of `container_name` is waiting for its turn.
+    pub fn contains(&self, container_name: &str) -> bool {
+        self.queued.contains(container_name)
+    }
+
+    /// Marks a job handed back by the worker as no longer waiting.
+    pub fn take(&mut self, job: &RestartJob) {
+        for name in &job.container_names {
+            self.queued.remove(name);
+        }
+    }
+}
+
+/// Hands jobs back one at a time, each once the previous one is done and
+/// its spacing has passed.
+async fn pace(
+    mut jobs: mpsc::UnboundedReceiver<RestartJob>,
+    ready: mpsc::UnboundedSender<ReadyRestart>,
+) {
+    let mut last_finished: Option<Instant> = None;
+    while let Some(job) = jobs.recv().await {
+        if let Some(at) = last_finished
+            .map(|finished| finished + job.spacing)
+            .filter(|at| *at > Instant::now())
+        {
+            info!(
+                "Restart of {} waits {}s (restart_spacing_seconds)",
+                job.compose_file.display(),
+                at.saturating_duration_since(Instant::now())
+                    .as_secs_f64()
+                    .ceil()
+            );
+            sleep_until(at).await;
+        }
+        let (done, finished) = oneshot::channel();
+        if ready.send(ReadyRestart { job, done }).is_err() {
+            return;
+        }
+        let _ = finished.await;
+        last_finished = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn jobs_are_handed_back_one_spacing_apart() {
+        let spacing = Duration::from_secs(60);
+        let (mut queue, mut ready) = RestartQueue::spawn();
+        queue.push(PathBuf::from("/a/compose.yml"), vec!["a".into()], spacing);
+        queue.push(PathBuf::from("/b/compose.yml"), vec!["b".into()], spacing);
+        assert!(queue.contains("a") && queue.contains("b"));
+
+        let first = ready.recv().await.unwrap();
+        assert_eq!(first.job.container_names, ["a"]);
+        queue.take(&first.job);
+        assert!(!queue.contains("a"));
+
+        // Nothing more while the first restart runs
+        tokio::time::sleep(Duration::from_secs(300)).await;
+        assert!(ready.try_recv().is_err());
+        first.done.send(()).unwrap();
+
+        let finished = Instant::now();
+        let second = ready.recv().await.unwrap();
+        assert_eq!(second.job.container_names, ["b"]);
+        assert_eq!(Instant::now() - finished, spacing);
+    }
+}
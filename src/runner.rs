@@ -0,0 +1,354 @@
+use anyhow::{Context, Result};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::{Command, Output};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+// =============================================================================
+// Command Execution
+// =============================================================================
+
+pub type RunFuture<'a> = Pin<Box<dyn Future<Output = io::Result<CommandOutput>> + Send + 'a>>;
+
+/// Executes external commands on behalf of `PodmanClient` and the command
+/// hooks. The system runner spawns real processes; `MockRunner` answers from
+/// a closure so callers can simulate podman without having it installed.
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, invocation: &Invocation) -> io::Result<CommandOutput>;
+
+    /// Runs a command that may take a while (hooks, probes, custom
+    /// restarts) without blocking the runtime, killing it once `timeout`
+    /// has passed; that error has kind `TimedOut`. By default answered
+    /// through `run`, for runners that never spawn anything.
+    fn run_with_timeout<'a>(
+        &'a self,
+        invocation: &'a Invocation,
+        _timeout: Duration,
+    ) -> RunFuture<'a> {
+        Box::pin(async move { self.run(invocation) })
+    }
+}
+
+/// Runs commands as child processes.
+#[derive(Debug, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, invocation: &Invocation) -> io::Result<CommandOutput> {
+        invocation.to_command().output().map(CommandOutput::from)
+    }
+
+    fn run_with_timeout<'a>(
+        &'a self,
+        invocation: &'a Invocation,
+        timeout: Duration,
+    ) -> RunFuture<'a> {
+        Box::pin(async move {
+            let mut command = tokio::process::Command::from(invocation.to_command());
+            // Dropping the output future on timeout kills the child
+            command.kill_on_drop(true);
+            match tokio::time::timeout(timeout, command.output()).await {
+                Ok(output) => output.map(CommandOutput::from),
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("still running after {}s", timeout.as_secs()),
+                )),
+            }
+        })
+    }
+}
+
+/// A command to run, built like `std::process::Command` and executed by the
+/// runner it was created with.
+#[derive(Clone)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<OsString>,
+    pub current_dir: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Invocation {
+    pub fn new(program: &str, runner: Arc<dyn CommandRunner>) -> Self {
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+            current_dir: None,
+            env: Vec::new(),
+            runner,
+        }
+    }
+
+    /// The equivalent `std::process::Command`, for long-lived processes
+    /// that do not fit the run-to-completion `CommandRunner` model.
+    pub fn to_command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        command
+    }
+
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn current_dir(&mut self, dir: &Path) -> &mut Self {
+        self.current_dir = Some(dir.to_path_buf());
+        self
+    }
+
+    pub fn output(&mut self) -> io::Result<CommandOutput> {
+        self.runner.run(self)
+    }
+
+    /// Like `output`, without blocking the runtime and killed after
+    /// `timeout` (an error of kind `TimedOut`).
+    pub async fn output_with_timeout(&self, timeout: Duration) -> io::Result<CommandOutput> {
+        self.runner.run_with_timeout(self, timeout).await
+    }
+
+    /// The arguments as strings, lossily converted; handy for matching in a
+    /// `MockRunner`.
+    pub fn arg_strings(&self) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandStatus {
+    /// `None` when the process was killed by a signal
+    pub code: Option<i32>,
+}
+
+impl CommandStatus {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+}
+
+impl fmt::Display for CommandStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "exit code {}", code),
+            None => f.write_str("a signal"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub status: CommandStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CommandOutput {
+    /// Exit code 0 with the given stdout.
+    pub fn success(stdout: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: CommandStatus { code: Some(0) },
+            stdout: stdout.into(),
+            stderr: Vec::new(),
+        }
+    }
+
+    /// The given exit code and stderr.
+    pub fn failure(code: i32, stderr: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: CommandStatus { code: Some(code) },
+            stdout: Vec::new(),
+            stderr: stderr.into(),
+        }
+    }
+}
+
+impl From<Output> for CommandOutput {
+    fn from(output: Output) -> Self {
+        Self {
+            status: CommandStatus {
+                code: output.status.code(),
+            },
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+    }
+}
+
+/// Runs a user-supplied shell command (a hook, custom restart) through its
+/// runner without blocking the runtime, killed after `timeout`. Its output
+/// is copied to the log as `[tag] subject: line`, stdout as info and stderr
+/// as warn; `what` names the command in errors.
+pub async fn run_logged(
+    invocation: &Invocation,
+    what: &str,
+    tag: &str,
+    subject: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let output = match invocation.output_with_timeout(timeout).await {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => anyhow::bail!(
+            "{} for {} timed out after {}s and was killed",
+            what,
+            subject,
+            timeout.as_secs()
+        ),
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Failed to spawn {} for {}", what.to_lowercase(), subject)
+            });
+        }
+    };
+
+    for line in String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+    {
+        info!("[{}] {}: {}", tag, subject, line);
+    }
+    for line in String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+    {
+        warn!("[{}] {}: {}", tag, subject, line);
+    }
+
+    if !output.status.success() {
+        anyhow::bail!("{} for {} exited with {}", what, subject, output.status);
+    }
+    Ok(())
+}
+
+type Responder = dyn Fn(&Invocation) -> io::Result<CommandOutput> + Send + Sync;
+
+/// Answers every command from a closure and records what was run, e.g.
+/// `podman ps` listing a chosen set of containers and `podman-compose up`
+/// always succeeding.
+pub struct MockRunner {
+    respond: Box<Responder>,
+    calls: Mutex<Vec<Vec<String>>>,
+}
+
+impl MockRunner {
+    pub fn new(
+        respond: impl Fn(&Invocation) -> io::Result<CommandOutput> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            respond: Box::new(respond),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Every command run so far, as program followed by its arguments.
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl CommandRunner for MockRunner {
+    fn run(&self, invocation: &Invocation) -> io::Result<CommandOutput> {
+        let mut call = vec![invocation.program.clone()];
+        call.extend(invocation.arg_strings());
+        self.calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(call);
+        (self.respond)(invocation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn shell(script: &str) -> Invocation {
+        let mut invocation = Invocation::new("sh", Arc::new(SystemRunner));
+        invocation.args(["-c", script]);
+        invocation
+    }
+
+    #[tokio::test]
+    async fn system_runner_kills_commands_after_timeout() {
+        let started = Instant::now();
+        let error = shell("sleep 5")
+            .output_with_timeout(Duration::from_millis(200))
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn run_logged_reports_the_exit_code() {
+        let error = run_logged(
+            &shell("echo out; echo err >&2; exit 3"),
+            "Hook",
+            "hook",
+            "web",
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(error.to_string(), "Hook for web exited with exit code 3");
+        run_logged(
+            &shell("true"),
+            "Hook",
+            "hook",
+            "web",
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn mock_runner_answers_timed_runs_through_its_closure() {
+        let runner = Arc::new(MockRunner::new(|_| Ok(CommandOutput::success("up"))));
+        let mut invocation = Invocation::new("podman", runner.clone());
+        invocation.args(["exec", "web", "true"]);
+
+        let output = invocation
+            .output_with_timeout(Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        assert_eq!(output.stdout, b"up");
+        assert_eq!(runner.calls(), vec![vec!["podman", "exec", "web", "true"]]);
+    }
+}
@@ -0,0 +1,436 @@
+use crate::logging::LogBuffer;
+use crate::metrics::Metrics;
+use crate::parse::parse_duration;
+use crate::state::Heartbeat;
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+// =============================================================================
+// Control Server
+// =============================================================================
+
+/// Lines returned by `/logs` without `?lines=`
+const DEFAULT_LOG_LINES: usize = 100;
+
+/// How long a client may stall while sending its request or reading the
+/// response before its connection is dropped
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connections served at once; further ones are answered 503 right away
+const MAX_CONNECTIONS: usize = 32;
+
+/// A command from the control server, handled by the monitor loop so that all
+/// state mutation stays on a single task.
+pub enum ControlRequest {
+    RestartNow {
+        container: String,
+        initiator: String,
+        reply: oneshot::Sender<ControlResponse>,
+    },
+    Inspect {
+        container: String,
+        reply: oneshot::Sender<ControlResponse>,
+    },
+    Explain {
+        container: String,
+        reply: oneshot::Sender<ControlResponse>,
+    },
+    Maintenance {
+        action: MaintenanceAction,
+        reply: oneshot::Sender<ControlResponse>,
+    },
+}
+
+/// What a `/maintenance` request asks for.
+pub enum MaintenanceAction {
+    Status,
+    /// Suspend restarts, for `duration` or until switched off
+    On {
+        duration: Option<Duration>,
+        initiator: String,
+    },
+    Off {
+        initiator: String,
+    },
+}
+
+pub struct ControlResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl ControlResponse {
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    /// Everything after `?`, empty without a query string
+    query: String,
+    /// Token from an `Authorization: Bearer` header
+    bearer_token: Option<String>,
+}
+
+/// Bearer-token protection of the control server. `/healthz` is always open.
+#[derive(Debug, Clone, Default)]
+pub struct ControlAuth {
+    /// Required for mutations (POST) when set
+    pub token: Option<String>,
+    /// Also require the token for read endpoints (`/metrics`, `/logs`,
+    /// `/containers`, `/explain`, `/maintenance`)
+    pub protect_reads: bool,
+}
+
+impl ControlAuth {
+    fn allows(&self, request: &HttpRequest) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        if request.method == "GET" && !self.protect_reads {
+            return true;
+        }
+        request
+            .bearer_token
+            .as_deref()
+            .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    }
+}
+
+/// Compares without an early exit, so response timing doesn't reveal how much
+/// of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Binds the control server and serves it from a background thread, one
+/// thread per connection (at most `MAX_CONNECTIONS`). Requests are forwarded
+/// to the monitor through `tx`.
+pub fn spawn_server(
+    listen: SocketAddr,
+    tx: mpsc::Sender<ControlRequest>,
+    heartbeat: Heartbeat,
+    metrics: Metrics,
+    logs: LogBuffer,
+    auth: ControlAuth,
+) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .with_context(|| format!("Failed to bind control server to {}", listen))?;
+    info!(
+        "Control server listening on {}{}",
+        listen,
+        if auth.token.is_some() {
+            " (token required)"
+        } else {
+            ""
+        }
+    );
+
+    thread::spawn(move || {
+        let active = Arc::new(AtomicUsize::new(0));
+        for stream in listener.incoming() {
+            match stream {
+                Ok(mut stream) => {
+                    let Some(slot) = ConnectionSlot::acquire(&active) else {
+                        warn!(
+                            "Rejecting control connection: {} already being served",
+                            MAX_CONNECTIONS
+                        );
+                        let busy = ControlResponse::new(503, "Too many connections");
+                        let _ = stream.set_write_timeout(Some(CONNECTION_TIMEOUT));
+                        let _ = write_response(&mut stream, &busy);
+                        continue;
+                    };
+                    let tx = tx.clone();
+                    let heartbeat = heartbeat.clone();
+                    let metrics = metrics.clone();
+                    let logs = logs.clone();
+                    let auth = auth.clone();
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        handle_connection(stream, tx, &heartbeat, &metrics, &logs, &auth)
+                    });
+                }
+                Err(e) => warn!("Failed to accept control connection: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// One of the `MAX_CONNECTIONS` connection slots, given back when dropped.
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn acquire(active: &Arc<AtomicUsize>) -> Option<Self> {
+        active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < MAX_CONNECTIONS).then_some(n + 1)
+            })
+            .ok()?;
+        Some(Self(Arc::clone(active)))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    tx: mpsc::Sender<ControlRequest>,
+    heartbeat: &Heartbeat,
+    metrics: &Metrics,
+    logs: &LogBuffer,
+    auth: &ControlAuth,
+) {
+    let peer = stream
+        .peer_addr()
+        .map(|addr: SocketAddr| addr.to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    // A client that never finishes its request would hold the thread forever
+    if let Err(e) = stream
+        .set_read_timeout(Some(CONNECTION_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(CONNECTION_TIMEOUT)))
+    {
+        debug!(
+            "Failed to set timeouts on control connection from {}: {}",
+            peer, e
+        );
+        return;
+    }
+
+    let response = match read_request(&stream) {
+        Ok(request) => {
+            debug!(
+                "Control request from {}: {} {}",
+                peer, request.method, request.path
+            );
+            if request.path.trim_matches('/') == "healthz" || auth.allows(&request) {
+                route(&request, &peer, &tx, heartbeat, metrics, logs)
+            } else {
+                warn!(
+                    "Rejected unauthenticated control request from {}: {} {}",
+                    peer, request.method, request.path
+                );
+                ControlResponse::new(401, "Missing or invalid bearer token")
+            }
+        }
+        Err(e) => ControlResponse::new(400, format!("Bad request: {:#}", e)),
+    };
+
+    if let Err(e) = write_response(&mut stream, &response) {
+        debug!("Failed to write control response to {}: {}", peer, e);
+    }
+}
+
+fn route(
+    request: &HttpRequest,
+    peer: &str,
+    tx: &mpsc::Sender<ControlRequest>,
+    heartbeat: &Heartbeat,
+    metrics: &Metrics,
+    logs: &LogBuffer,
+) -> ControlResponse {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["healthz"]) => healthz(heartbeat),
+        (_, ["healthz"]) => ControlResponse::new(405, "Method not allowed"),
+        ("GET", ["metrics"]) => ControlResponse::new(200, metrics.render()),
+        (_, ["metrics"]) => ControlResponse::new(405, "Method not allowed"),
+        ("GET", ["logs"]) => recent_logs(logs, &request.query),
+        (_, ["logs"]) => ControlResponse::new(405, "Method not allowed"),
+        ("POST", ["restart", container]) if !container.is_empty() => {
+            let (reply, response) = oneshot::channel();
+            let command = ControlRequest::RestartNow {
+                container: container.to_string(),
+                initiator: format!("control API ({})", peer),
+                reply,
+            };
+            dispatch(tx, command, response)
+        }
+        (_, ["restart", _]) => ControlResponse::new(405, "Method not allowed"),
+        ("GET", ["containers", container]) if !container.is_empty() => {
+            let (reply, response) = oneshot::channel();
+            let command = ControlRequest::Inspect {
+                container: container.to_string(),
+                reply,
+            };
+            dispatch(tx, command, response)
+        }
+        (_, ["containers", _]) => ControlResponse::new(405, "Method not allowed"),
+        ("GET", ["explain", container]) if !container.is_empty() => {
+            let (reply, response) = oneshot::channel();
+            let command = ControlRequest::Explain {
+                container: container.to_string(),
+                reply,
+            };
+            dispatch(tx, command, response)
+        }
+        (_, ["explain", _]) => ControlResponse::new(405, "Method not allowed"),
+        ("GET", ["maintenance"]) => maintenance(tx, MaintenanceAction::Status),
+        ("POST", ["maintenance", "on"]) => maintenance(
+            tx,
+            MaintenanceAction::On {
+                duration: None,
+                initiator: format!("control API ({})", peer),
+            },
+        ),
+        ("POST", ["maintenance", "on", duration]) => match parse_duration(duration) {
+            Some(duration) if !duration.is_zero() => maintenance(
+                tx,
+                MaintenanceAction::On {
+                    duration: Some(duration),
+                    initiator: format!("control API ({})", peer),
+                },
+            ),
+            _ => ControlResponse::new(
+                400,
+                format!("Invalid duration: {} (e.g. 30m, 2h, 1h30m)", duration),
+            ),
+        },
+        ("POST", ["maintenance", "off"]) => maintenance(
+            tx,
+            MaintenanceAction::Off {
+                initiator: format!("control API ({})", peer),
+            },
+        ),
+        (_, ["maintenance", ..]) => ControlResponse::new(405, "Method not allowed"),
+        _ => ControlResponse::new(404, "Not found"),
+    }
+}
+
+/// Answered on the connection thread, so it keeps working (and failing) when
+/// the monitor loop is stuck.
+fn healthz(heartbeat: &Heartbeat) -> ControlResponse {
+    match heartbeat.age() {
+        None => ControlResponse::new(200, "starting"),
+        Some((age, max_age)) if age <= max_age => {
+            ControlResponse::new(200, format!("ok: last check {}s ago", age.as_secs()))
+        }
+        Some((age, max_age)) => ControlResponse::new(
+            503,
+            format!(
+                "stale: last successful check {}s ago (limit {}s)",
+                age.as_secs(),
+                max_age.as_secs()
+            ),
+        ),
+    }
+}
+
+/// The last `lines` (default `DEFAULT_LOG_LINES`) buffered log lines.
+/// Answered on the connection thread like `/healthz`.
+fn recent_logs(logs: &LogBuffer, query: &str) -> ControlResponse {
+    if logs.capacity() == 0 {
+        return ControlResponse::new(404, "Log buffer disabled (log_buffer_lines = 0)");
+    }
+    let lines = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("lines="))
+        .map_or(Some(DEFAULT_LOG_LINES), |value| value.parse().ok());
+    let Some(lines) = lines else {
+        return ControlResponse::new(400, "Invalid lines: expected a number, e.g. lines=200");
+    };
+    let mut body = logs.tail(lines).join("\n");
+    if !body.is_empty() {
+        body.push('\n');
+    }
+    ControlResponse::new(200, body)
+}
+
+fn maintenance(tx: &mpsc::Sender<ControlRequest>, action: MaintenanceAction) -> ControlResponse {
+    let (reply, response) = oneshot::channel();
+    dispatch(tx, ControlRequest::Maintenance { action, reply }, response)
+}
+
+fn dispatch(
+    tx: &mpsc::Sender<ControlRequest>,
+    command: ControlRequest,
+    response: oneshot::Receiver<ControlResponse>,
+) -> ControlResponse {
+    if tx.blocking_send(command).is_err() {
+        return ControlResponse::new(503, "Monitor is not running");
+    }
+    response
+        .blocking_recv()
+        .unwrap_or_else(|_| ControlResponse::new(500, "Monitor dropped the request"))
+}
+
+fn read_request(stream: &TcpStream) -> Result<HttpRequest> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .context("Failed to read request line")?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().context("Missing method")?.to_string();
+    let target = parts.next().context("Missing path")?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    // Read headers up to the blank line; no endpoint takes a request body
+    let mut bearer_token = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("authorization")
+        {
+            bearer_token = value
+                .trim()
+                .strip_prefix("Bearer ")
+                .map(|token| token.trim().to_string());
+        }
+    }
+
+    Ok(HttpRequest {
+        method,
+        path,
+        query,
+        bearer_token,
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: &ControlResponse) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,48 @@
+use anyhow::{Context, Result};
+use std::sync::OnceLock;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::watch;
+
+// =============================================================================
+// Graceful Shutdown
+// =============================================================================
+
+pub const SIGTERM: i32 = 15;
+
+/// Flips to `true` once, when shutdown is requested; every `wait` watches it.
+static SHUTDOWN_REQUESTED: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+fn flag() -> &'static watch::Sender<bool> {
+    SHUTDOWN_REQUESTED.get_or_init(|| watch::channel(false).0)
+}
+
+/// Routes SIGINT and SIGTERM to a shutdown request instead of killing the
+/// process. Must be called from within the Tokio runtime.
+pub fn install_handlers() -> Result<()> {
+    let mut terminate =
+        signal(SignalKind::terminate()).context("Failed to install the SIGTERM handler")?;
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        request();
+    });
+    Ok(())
+}
+
+/// Asks everything waiting in `wait` to shut down, as a signal would.
+pub fn request() {
+    flag().send_replace(true);
+}
+
+pub fn requested() -> bool {
+    *flag().borrow()
+}
+
+/// Resolves once shutdown has been requested.
+pub async fn wait() {
+    let mut requested = flag().subscribe();
+    // The sender lives in a static, so this cannot fail
+    let _ = requested.wait_for(|requested| *requested).await;
+}
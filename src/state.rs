@@ -1,51 +1,291 @@
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
-use std::time::Duration;
+use crate::clock::{Clock, SystemClock};
+use crate::metrics::Metrics;
+use crate::notify::format_timestamp;
+use crate::parse::{ContainerSpec, HealthcheckSpec, NamePattern, RestartPolicy, SourceKind};
+use crate::podman::ContainerExit;
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use tokio::time::Instant;
 
 // =============================================================================
 // Container State Management
 // =============================================================================
 
+/// What triggered a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartReason {
+    /// Not running, with no more specific cause known
+    Down,
+    /// Exited with a non-zero code (requires `inspect_exit_codes`)
+    Crashed,
+    /// Killed by the kernel OOM killer (requires `inspect_exit_codes`)
+    OomKilled,
+    /// Running but failing its healthcheck probe
+    Unhealthy,
+    /// Requested through the control API
+    Manual,
+    /// Recreated to run a newer image (`restart_on_image_update`)
+    ImageUpdate,
+}
+
+impl RestartReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestartReason::Down => "down",
+            RestartReason::Crashed => "crashed",
+            RestartReason::OomKilled => "oom_killed",
+            RestartReason::Unhealthy => "unhealthy",
+            RestartReason::Manual => "manual",
+            RestartReason::ImageUpdate => "image_update",
+        }
+    }
+}
+
+/// Result of one restart attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartOutcome {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RestartRecord {
+    pub timestamp: SystemTime,
+    pub reason: Option<RestartReason>,
+    pub outcome: RestartOutcome,
+}
+
 #[derive(Debug, Clone)]
 pub struct ContainerState {
     pub compose_file: PathBuf,
+    pub source: SourceKind,
+    pub restart_policy: RestartPolicy,
     last_restart: Option<Instant>,
-    pub restart_count: u32,
+    last_attempt: Option<Instant>,
+    pub awaiting_start: bool,
+    pub reported_down: bool,
+    /// Check cycles in a row the container was seen not running.
+    pub consecutive_down_checks: u32,
+    /// When the container was first seen not running in its current outage
+    down_since: Option<Instant>,
+    /// How long its most recent finished outage lasted
+    pub last_downtime: Option<Duration>,
+    /// Restart attempts, counted when they start.
+    pub restart_attempts: u32,
+    /// Attempts verified to have brought the container back.
+    pub restart_successes: u32,
     pub consecutive_failures: u32,
+    /// Times the failure budget was exhausted since the container was last
+    /// (re-)enabled.
+    pub giveups: u32,
+    /// Out of management after too many give-ups, until re-enabled.
+    pub abandoned: bool,
+    pub healthcheck: Option<HealthcheckSpec>,
+    pub last_exit: Option<ContainerExit>,
+    /// Trigger of the most recent restart attempt.
+    pub last_restart_reason: Option<RestartReason>,
+    /// Most recent restart attempts, oldest first, capped at
+    /// `restart_history_size`.
+    pub history: VecDeque<RestartRecord>,
+    pub max_retries: Option<u32>,
+    pub label_priority: Option<i32>,
+    pub name_pattern: Option<NamePattern>,
+    pub image: Option<String>,
+    clock: Arc<dyn Clock>,
 }
 
 impl ContainerState {
-    pub fn new(compose_file: PathBuf) -> Self {
+    pub fn new(compose_file: PathBuf, spec: &ContainerSpec, clock: Arc<dyn Clock>) -> Self {
         Self {
             compose_file,
+            source: spec.source,
+            restart_policy: spec.restart_policy,
             last_restart: None,
-            restart_count: 0,
+            last_attempt: None,
+            awaiting_start: false,
+            reported_down: false,
+            consecutive_down_checks: 0,
+            down_since: None,
+            last_downtime: None,
+            restart_attempts: 0,
+            restart_successes: 0,
             consecutive_failures: 0,
+            giveups: 0,
+            abandoned: false,
+            healthcheck: spec.healthcheck.clone(),
+            last_exit: None,
+            last_restart_reason: None,
+            history: VecDeque::new(),
+            max_retries: spec.max_retries,
+            label_priority: spec.priority,
+            name_pattern: spec.name_pattern.clone(),
+            image: spec.image.clone(),
+            clock,
         }
     }
 
+    fn update_spec(&mut self, compose_file: PathBuf, spec: &ContainerSpec) {
+        self.compose_file = compose_file;
+        self.source = spec.source;
+        self.restart_policy = spec.restart_policy;
+        self.healthcheck = spec.healthcheck.clone();
+        self.max_retries = spec.max_retries;
+        self.label_priority = spec.priority;
+        self.name_pattern = spec.name_pattern.clone();
+        self.image = spec.image.clone();
+        self.reenable();
+    }
+
+    /// Consecutive failures allowed before giving up, or `None` to never give
+    /// up. A compose label or `on-failure:N` policy takes precedence over the
+    /// global default; with `policy_aware`, `restart: always` containers
+    /// without such an explicit cap are retried indefinitely.
+    pub fn failure_cap(&self, default: u32, policy_aware: bool) -> Option<u32> {
+        match self.max_retries {
+            Some(cap) => Some(cap),
+            None if policy_aware && self.restart_policy == RestartPolicy::Always => None,
+            None => Some(default),
+        }
+    }
+
+    pub fn is_exhausted(&self, default: u32, policy_aware: bool) -> bool {
+        self.failure_cap(default, policy_aware)
+            .is_some_and(|cap| self.consecutive_failures >= cap)
+    }
+
+    /// Whether the container is in `running`: by exact name, or for a
+    /// compose-generated name, by its name pattern.
+    pub fn is_running_in(&self, name: &str, running: &HashSet<String>) -> bool {
+        running.contains(name)
+            || self
+                .name_pattern
+                .as_ref()
+                .is_some_and(|pattern| running.iter().any(|live| pattern.matches(live)))
+    }
+
     pub fn backoff_duration(&self) -> Duration {
         let backoff_seconds = 2_u64.pow(self.consecutive_failures.min(6));
         Duration::from_secs(backoff_seconds)
     }
 
+    /// Time left before backoff allows another restart.
+    pub fn backoff_remaining(&self) -> Duration {
+        self.last_restart
+            .map(|time| {
+                self.backoff_duration()
+                    .saturating_sub(self.clock.elapsed(time))
+            })
+            .unwrap_or_default()
+    }
+
     pub fn is_in_backoff(&self) -> bool {
         self.last_restart
-            .map(|time| time.elapsed() < self.backoff_duration())
+            .map(|time| self.clock.elapsed(time) < self.backoff_duration())
+            .unwrap_or(false)
+    }
+
+    /// True while a just-restarted container is still inside its healthcheck
+    /// `start_period`, during which probe failures are not meaningful.
+    pub fn in_healthcheck_start_period(&self) -> bool {
+        let start_period = self
+            .healthcheck
+            .as_ref()
+            .and_then(|healthcheck| healthcheck.start_period);
+
+        match (start_period, self.last_restart) {
+            (Some(period), Some(time)) => self.clock.elapsed(time) < period,
+            _ => false,
+        }
+    }
+
+    pub fn record_attempt(&mut self) {
+        self.restart_attempts += 1;
+        self.last_attempt = Some(self.clock.now());
+    }
+
+    /// Share of restart attempts that succeeded, or `None` before the first
+    /// attempt. Attempts still being verified count as not (yet) successful.
+    pub fn success_ratio(&self) -> Option<f64> {
+        (self.restart_attempts > 0)
+            .then(|| self.restart_successes as f64 / self.restart_attempts as f64)
+    }
+
+    /// True while the most recent restart attempt is younger than `grace`.
+    pub fn in_start_grace(&self, grace: Duration) -> bool {
+        self.last_attempt
+            .map(|time| self.clock.elapsed(time) < grace)
             .unwrap_or(false)
     }
 
+    /// Clears accumulated failures and backoff, e.g. when an operator forces
+    /// a restart.
+    pub fn reset_backoff(&mut self) {
+        self.consecutive_failures = 0;
+        self.last_restart = None;
+    }
+
+    /// Returns an abandoned container to active management.
+    pub fn reenable(&mut self) {
+        self.abandoned = false;
+        self.giveups = 0;
+    }
+
+    /// Counts an exhausted failure budget and abandons the container once
+    /// `max_giveups` is reached (0 never abandons). Returns true if this
+    /// give-up abandoned it.
+    pub fn record_giveup(&mut self, max_giveups: u32) -> bool {
+        self.giveups += 1;
+        if max_giveups > 0 && self.giveups >= max_giveups {
+            self.abandoned = true;
+        }
+        self.abandoned
+    }
+
+    /// Appends a restart attempt to the history, dropping the oldest entries
+    /// beyond `capacity`.
+    pub fn record_history(&mut self, outcome: RestartOutcome, capacity: usize) {
+        self.history.push_back(RestartRecord {
+            timestamp: SystemTime::now(),
+            reason: self.last_restart_reason,
+            outcome,
+        });
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
+    }
+
+    fn begin_outage(&mut self) {
+        if self.down_since.is_none() {
+            self.down_since = Some(self.clock.now());
+        }
+    }
+
+    /// Ends the current outage, if any, returning its length.
+    fn end_outage(&mut self) -> Option<Duration> {
+        let downtime = self.clock.elapsed(self.down_since.take()?);
+        self.last_downtime = Some(downtime);
+        Some(downtime)
+    }
+
     pub fn record_success(&mut self) {
-        self.restart_count += 1;
-        self.last_restart = Some(Instant::now());
+        self.awaiting_start = false;
+        self.restart_successes += 1;
+        self.last_restart = Some(self.clock.now());
         self.consecutive_failures = 0;
     }
 
     pub fn record_failure(&mut self) {
+        self.awaiting_start = false;
         self.consecutive_failures += 1;
         if self.last_restart.is_none() {
-            self.last_restart = Some(Instant::now());
+            self.last_restart = Some(self.clock.now());
         }
     }
 }
@@ -54,37 +294,429 @@ impl ContainerState {
 pub struct MonitorState {
     pub managed_containers: HashMap<String, ContainerState>,
     running_containers: HashSet<String>,
+    /// Lifetime counters for the shutdown summary
+    pub total_restarts: u64,
+    pub total_failures: u64,
+    pub start_time: Instant,
+    pub last_successful_check: Heartbeat,
+    pub metrics: Metrics,
+    /// When each managed container is next evaluated for a restart.
+    pub schedule: CheckSchedule,
+    /// Last restart of each compose (or Kubernetes YAML) file's stack.
+    stack_restarts: HashMap<PathBuf, Instant>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for MonitorState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MonitorState {
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Like `new`, but every time-based decision (backoff, grace periods,
+    /// throttling, scheduling) reads `clock`.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
             managed_containers: HashMap::new(),
             running_containers: HashSet::new(),
+            total_restarts: 0,
+            total_failures: 0,
+            start_time: clock.now(),
+            last_successful_check: Heartbeat::default(),
+            metrics: Metrics::default(),
+            schedule: CheckSchedule::default(),
+            stack_restarts: HashMap::new(),
+            clock,
         }
     }
 
-    pub fn update_running(&mut self, running: HashSet<String>) {
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Wall-clock time by this state's clock.
+    pub fn wall_now(&self) -> SystemTime {
+        self.clock.wall_now()
+    }
+
+    /// Time since `earlier` by this state's clock.
+    pub fn elapsed(&self, earlier: Instant) -> Duration {
+        self.clock.elapsed(earlier)
+    }
+
+    pub fn record_stack_restart(&mut self, compose_file: &Path) {
+        self.stack_restarts
+            .insert(compose_file.to_path_buf(), self.clock.now());
+    }
+
+    /// Time left before the stack of `compose_file` may be restarted again,
+    /// or `None` once `min_interval` has passed since its last restart.
+    pub fn stack_restart_remaining(
+        &self,
+        compose_file: &Path,
+        min_interval: Duration,
+    ) -> Option<Duration> {
+        let last = self.stack_restarts.get(compose_file)?;
+        min_interval
+            .checked_sub(self.clock.elapsed(*last))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Replaces the set of running containers with a fresh observation and
+    /// updates each managed container's run of down checks. Only containers
+    /// in `due` count a down check: a cycle run early for another container
+    /// (a podman event, a control request) must not rush `down_confirmations`.
+    /// Returns the containers whose outage just ended, with how long they
+    /// were down.
+    pub fn update_running(
+        &mut self,
+        running: HashSet<String>,
+        due: &HashSet<String>,
+    ) -> Vec<(String, Duration)> {
+        let mut recovered = Vec::new();
+        for (name, state) in self.managed_containers.iter_mut() {
+            if state.is_running_in(name, &running) {
+                state.consecutive_down_checks = 0;
+                recovered.extend(state.end_outage().map(|downtime| (name.clone(), downtime)));
+            } else {
+                if due.contains(name) {
+                    state.consecutive_down_checks += 1;
+                }
+                state.begin_outage();
+            }
+        }
+        self.running_containers = running;
+        recovered.sort();
+        recovered
+    }
+
+    /// Takes in a running set queried outside a check cycle (after a
+    /// restart): containers seen running start a fresh run of down checks,
+    /// but nothing counts as a down check. Returns ended outages like
+    /// `update_running`.
+    pub fn observe_running(&mut self, running: HashSet<String>) -> Vec<(String, Duration)> {
+        let mut recovered = Vec::new();
+        for (name, state) in self.managed_containers.iter_mut() {
+            if state.is_running_in(name, &running) {
+                state.consecutive_down_checks = 0;
+                recovered.extend(state.end_outage().map(|downtime| (name.clone(), downtime)));
+            } else {
+                state.begin_outage();
+            }
+        }
         self.running_containers = running;
+        recovered.sort();
+        recovered
     }
 
     pub fn running_managed_count(&self) -> usize {
         self.managed_containers
-            .keys()
-            .filter(|name| self.running_containers.contains(*name))
+            .iter()
+            .filter(|(name, state)| state.is_running_in(name, &self.running_containers))
             .count()
     }
 
-    pub fn clear_managed(&mut self) {
-        self.managed_containers.clear();
+    /// Drops state for containers that no longer appear in any compose file
+    /// and returns what was removed, sorted by name.
+    pub fn prune_managed(&mut self, discovered: &HashSet<String>) -> Vec<(String, ContainerState)> {
+        let orphaned: Vec<String> = self
+            .managed_containers
+            .keys()
+            .filter(|name| !discovered.contains(*name))
+            .cloned()
+            .collect();
+
+        let mut removed: Vec<(String, ContainerState)> = orphaned
+            .into_iter()
+            .filter_map(|name| {
+                let state = self.managed_containers.remove(&name)?;
+                self.schedule.remove(&name);
+                Some((name, state))
+            })
+            .collect();
+        removed.sort_by(|a, b| a.0.cmp(&b.0));
+        removed
     }
 
-    pub fn add_container(&mut self, name: String, compose_file: PathBuf) {
-        self.managed_containers
-            .insert(name, ContainerState::new(compose_file));
+    /// Adds a newly discovered container, or refreshes the compose-derived
+    /// settings of one already managed while keeping its restart history and
+    /// backoff, so a config reload doesn't grant a crash-looping container a
+    /// fresh set of aggressive retries.
+    pub fn add_container(&mut self, spec: ContainerSpec, compose_file: PathBuf) {
+        match self.managed_containers.get_mut(&spec.name) {
+            Some(state) => state.update_spec(compose_file, &spec),
+            None => {
+                let state = ContainerState::new(compose_file, &spec, Arc::clone(&self.clock));
+                self.schedule.schedule(&spec.name, self.clock.now());
+                self.managed_containers.insert(spec.name, state);
+            }
+        }
+    }
+
+    /// Resets the failure budget of exhausted (but not abandoned) containers
+    /// that are running again. Returns their names, sorted.
+    pub fn reset_recovered(&mut self, max_failures: u32, policy_aware: bool) -> Vec<String> {
+        let mut recovered = Vec::new();
+        for (name, state) in self.managed_containers.iter_mut() {
+            if !state.abandoned
+                && state.is_running_in(name, &self.running_containers)
+                && state.is_exhausted(max_failures, policy_aware)
+            {
+                state.reset_backoff();
+                recovered.push(name.clone());
+            }
+        }
+        recovered.sort();
+        recovered
     }
 
     pub fn is_running(&self, name: &str) -> bool {
-        self.running_containers.contains(name)
+        match self.managed_containers.get(name) {
+            Some(state) => state.is_running_in(name, &self.running_containers),
+            None => self.running_containers.contains(name),
+        }
+    }
+
+    /// Managed containers ordered by compose file, then name, so periodic
+    /// output is stable between cycles.
+    pub fn sorted_containers(&self) -> Vec<(&String, &ContainerState)> {
+        let mut containers: Vec<_> = self.managed_containers.iter().collect();
+        containers.sort_by(|(a_name, a), (b_name, b)| {
+            a.compose_file
+                .cmp(&b.compose_file)
+                .then_with(|| a_name.cmp(b_name))
+        });
+        containers
+    }
+
+    /// Everything known about one managed container, or `None` if it is not
+    /// managed.
+    pub fn detail(&self, name: &str) -> Option<ContainerDetail> {
+        let state = self.managed_containers.get(name)?;
+        Some(ContainerDetail {
+            name: name.to_string(),
+            compose_file: state.compose_file.display().to_string(),
+            image: state.image.clone(),
+            running: self.is_running(name),
+            restart_count: state.restart_successes,
+            restart_attempts: state.restart_attempts,
+            success_ratio: state.success_ratio(),
+            consecutive_failures: state.consecutive_failures,
+            giveups: state.giveups,
+            abandoned: state.abandoned,
+            awaiting_start: state.awaiting_start,
+            backoff_remaining_seconds: state.backoff_remaining().as_secs(),
+            last_restart: state
+                .last_restart
+                .map(|time| format_timestamp(SystemTime::now() - self.clock.elapsed(time))),
+            last_exit_code: state.last_exit.map(|exit| exit.exit_code),
+            last_restart_reason: state.last_restart_reason,
+            oom_killed: state.last_exit.is_some_and(|exit| exit.oom_killed),
+            down_since: state
+                .down_since
+                .map(|time| format_timestamp(SystemTime::now() - self.clock.elapsed(time))),
+            last_downtime_seconds: state.last_downtime.map(|downtime| downtime.as_secs()),
+            history: history_report(state),
+        })
+    }
+
+    /// Builds a serializable snapshot of the current state, in the order of
+    /// `sorted_containers`.
+    pub fn report(&self) -> StatusReport {
+        let containers: Vec<ContainerReport> = self
+            .sorted_containers()
+            .into_iter()
+            .map(|(name, state)| ContainerReport {
+                name: name.clone(),
+                compose_file: state.compose_file.display().to_string(),
+                image: state.image.clone(),
+                running: self.is_running(name),
+                restart_count: state.restart_successes,
+                restart_attempts: state.restart_attempts,
+                success_ratio: state.success_ratio(),
+                consecutive_failures: state.consecutive_failures,
+                abandoned: state.abandoned,
+                last_restart: state
+                    .last_restart
+                    .map(|time| format_timestamp(SystemTime::now() - self.clock.elapsed(time))),
+                last_exit_code: state.last_exit.map(|exit| exit.exit_code),
+                last_restart_reason: state.last_restart_reason,
+                down_since: state
+                    .down_since
+                    .map(|time| format_timestamp(SystemTime::now() - self.clock.elapsed(time))),
+                last_downtime_seconds: state.last_downtime.map(|downtime| downtime.as_secs()),
+                history: history_report(state),
+            })
+            .collect();
+
+        StatusReport {
+            generated_at: format_timestamp(SystemTime::now()),
+            managed: self.managed_containers.len(),
+            running: self.running_managed_count(),
+            maintenance: false,
+            containers,
+        }
+    }
+}
+
+/// Next evaluation time per container, as a min-heap. Rescheduling pushes a
+/// new entry and leaves the old one in place; entries that no longer match
+/// `due_at` are discarded when they reach the top.
+#[derive(Debug, Default)]
+pub struct CheckSchedule {
+    heap: BinaryHeap<Reverse<(Instant, String)>>,
+    due_at: HashMap<String, Instant>,
+}
+
+impl CheckSchedule {
+    pub fn schedule(&mut self, name: &str, at: Instant) {
+        self.due_at.insert(name.to_string(), at);
+        self.heap.push(Reverse((at, name.to_string())));
     }
+
+    pub fn remove(&mut self, name: &str) {
+        self.due_at.remove(name);
+    }
+
+    /// The earliest scheduled evaluation, if any container is scheduled.
+    pub fn next_due(&mut self) -> Option<Instant> {
+        self.discard_stale();
+        self.heap.peek().map(|Reverse((at, _))| *at)
+    }
+
+    /// Removes and returns every container due at or before `now`. They
+    /// stay unscheduled until rescheduled.
+    pub fn take_due(&mut self, now: Instant) -> HashSet<String> {
+        let mut due = HashSet::new();
+        loop {
+            self.discard_stale();
+            match self.heap.peek() {
+                Some(Reverse((at, _))) if *at <= now => {
+                    let Some(Reverse((_, name))) = self.heap.pop() else {
+                        break;
+                    };
+                    self.due_at.remove(&name);
+                    due.insert(name);
+                }
+                _ => break,
+            }
+        }
+        due
+    }
+
+    fn discard_stale(&mut self) {
+        while let Some(Reverse((at, name))) = self.heap.peek() {
+            if self.due_at.get(name) == Some(at) {
+                break;
+            }
+            self.heap.pop();
+        }
+    }
+}
+
+/// Time of the last completed check cycle, and how old it may get before the
+/// monitor counts as stuck. Cloned into the control server so `/healthz` can
+/// answer without a round trip through the monitor loop, which is exactly
+/// what it must detect being stuck.
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeat(Arc<Mutex<Option<(Instant, Duration)>>>);
+
+impl Heartbeat {
+    pub fn beat(&self, max_age: Duration) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), max_age));
+    }
+
+    /// Time since the last beat together with its `max_age`, or `None`
+    /// before the first beat.
+    pub fn age(&self) -> Option<(Duration, Duration)> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .map(|(time, max_age)| (time.elapsed(), max_age))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub generated_at: String,
+    pub managed: usize,
+    pub running: usize,
+    /// Restarts are suspended by maintenance mode
+    pub maintenance: bool,
+    pub containers: Vec<ContainerReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContainerReport {
+    pub name: String,
+    pub compose_file: String,
+    /// `None` for services that are only built
+    pub image: Option<String>,
+    pub running: bool,
+    /// Successful restarts
+    pub restart_count: u32,
+    pub restart_attempts: u32,
+    pub success_ratio: Option<f64>,
+    pub consecutive_failures: u32,
+    pub abandoned: bool,
+    pub last_restart: Option<String>,
+    pub last_exit_code: Option<i32>,
+    pub last_restart_reason: Option<RestartReason>,
+    /// Start of the current outage
+    pub down_since: Option<String>,
+    /// Length of the most recent finished outage
+    pub last_downtime_seconds: Option<u64>,
+    pub history: Vec<RestartRecordReport>,
+}
+
+/// Per-container view served by the control API and printed by `inspect`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContainerDetail {
+    pub name: String,
+    pub compose_file: String,
+    /// `None` for services that are only built
+    pub image: Option<String>,
+    pub running: bool,
+    /// Successful restarts
+    pub restart_count: u32,
+    pub restart_attempts: u32,
+    pub success_ratio: Option<f64>,
+    pub consecutive_failures: u32,
+    pub giveups: u32,
+    pub abandoned: bool,
+    pub awaiting_start: bool,
+    pub backoff_remaining_seconds: u64,
+    pub last_restart: Option<String>,
+    pub last_exit_code: Option<i32>,
+    pub last_restart_reason: Option<RestartReason>,
+    pub oom_killed: bool,
+    /// Start of the current outage
+    pub down_since: Option<String>,
+    /// Length of the most recent finished outage
+    pub last_downtime_seconds: Option<u64>,
+    pub history: Vec<RestartRecordReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestartRecordReport {
+    pub timestamp: String,
+    pub reason: Option<RestartReason>,
+    pub outcome: RestartOutcome,
+}
+
+fn history_report(state: &ContainerState) -> Vec<RestartRecordReport> {
+    state
+        .history
+        .iter()
+        .map(|record| RestartRecordReport {
+            timestamp: format_timestamp(record.timestamp),
+            reason: record.reason,
+            outcome: record.outcome,
+        })
+        .collect()
 }
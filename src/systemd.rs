@@ -0,0 +1,74 @@
+use std::env;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use tracing::debug;
+use tracing::warn;
+
+// =============================================================================
+// systemd Notifications
+// =============================================================================
+
+/// Tells systemd that startup (discovery and recovery) has finished. Only has
+/// an effect with `Type=notify`.
+pub fn notify_ready() {
+    notify("READY=1");
+}
+
+/// Resets the `WatchdogSec=` timer; sent after every successful check cycle.
+pub fn notify_watchdog() {
+    notify("WATCHDOG=1");
+}
+
+pub fn notify_stopping() {
+    notify("STOPPING=1");
+}
+
+/// The watchdog timeout systemd expects us to beat within, if enabled.
+fn watchdog_timeout() -> Option<Duration> {
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec))
+}
+
+/// Warns when the watchdog would fire between two regular check cycles.
+pub fn check_watchdog_interval(check_interval: Duration) {
+    if let Some(timeout) = watchdog_timeout()
+        && timeout <= check_interval
+    {
+        warn!(
+            "WatchdogSec ({}s) is not longer than check_interval_seconds ({}s); systemd will kill the monitor between checks",
+            timeout.as_secs(),
+            check_interval.as_secs()
+        );
+    }
+}
+
+/// Sends one `sd_notify` datagram to `$NOTIFY_SOCKET`. A no-op when not run
+/// by systemd (or not as `Type=notify`), so callers never need to check.
+#[cfg(target_os = "linux")]
+fn notify(state: &str) {
+    let Ok(path) = env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    let result = (|| {
+        // A leading '@' denotes a socket in the abstract namespace
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(&path)?,
+        };
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to_addr(state.as_bytes(), &addr)
+    })();
+
+    if let Err(e) = result {
+        debug!("Failed to send {} to NOTIFY_SOCKET {}: {}", state, path, e);
+    }
+}
+
+/// systemd only exists on Linux; elsewhere there is nobody to notify.
+#[cfg(not(target_os = "linux"))]
+fn notify(_state: &str) {}
@@ -0,0 +1,252 @@
+use crate::cli_config::Config;
+use crate::json::to_json;
+use crate::monitor::{discover, labeled_containers};
+use crate::parse::{ComposeParser, ServiceDecision};
+use crate::podman::{ComposeTool, PodmanClient};
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// =============================================================================
+// Configuration Validation
+// =============================================================================
+
+/// Result of `--validate` / `--config-test`, which doubles as its exit code.
+/// When problems of several kinds are found, the lowest code wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Validation {
+    /// Exit 0: the config loads and every enabled compose file parses
+    Ok,
+    /// Exit 1: the config itself is invalid (unreadable, rejected values,
+    /// container names claimed by two compose files)
+    ConfigError,
+    /// Exit 2: an enabled compose file does not exist
+    MissingComposeFile,
+    /// Exit 3: a compose file exists but cannot be read or parsed
+    ParseError,
+}
+
+impl Validation {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Validation::Ok => 0,
+            Validation::ConfigError => 1,
+            Validation::MissingComposeFile => 2,
+            Validation::ParseError => 3,
+        }
+    }
+
+    fn combine(self, other: Validation) -> Validation {
+        match (self, other) {
+            (Validation::Ok, other) | (other, Validation::Ok) => other,
+            (a, b) => a.min(b),
+        }
+    }
+}
+
+/// Checks every compose file the way discovery would, without touching
+/// podman. Prints a line per compose file and per problem found, and returns
+/// the kind of problem found, if any.
+pub fn validate(config: &Config) -> Validation {
+    let mut owners: HashMap<String, PathBuf> = HashMap::new();
+    let mut problems = 0;
+    let mut outcome = Validation::Ok;
+
+    for compose_file in &config.compose_files {
+        let compose_path_str = &compose_file.path;
+        if !compose_file.enabled {
+            println!("skip: {} (disabled)", compose_path_str);
+            continue;
+        }
+        let compose_path = PathBuf::from(compose_path_str);
+
+        if !compose_path.exists() {
+            println!("error: compose file not found: {}", compose_path_str);
+            problems += 1;
+            outcome = outcome.combine(Validation::MissingComposeFile);
+            continue;
+        }
+
+        if let Some(dir) = &compose_file.working_dir
+            && !dir.is_dir()
+        {
+            println!(
+                "error: working_dir of {} is not a directory: {}",
+                compose_path_str,
+                dir.display()
+            );
+            problems += 1;
+            outcome = outcome.combine(Validation::ConfigError);
+        }
+
+        let parse_options = config.file_parse_options(compose_file);
+        let containers = match ComposeParser::parse_containers(&compose_path, &parse_options) {
+            Ok(containers) => containers,
+            Err(e) => {
+                println!("error: {:#}", e);
+                problems += 1;
+                outcome = outcome.combine(Validation::ParseError);
+                continue;
+            }
+        };
+
+        println!("ok: {} ({} containers)", compose_path_str, containers.len());
+        for container in containers {
+            if let Some(owner) = owners.get(&container.name) {
+                println!(
+                    "error: container name collision: {} is defined in both {} and {}",
+                    container.name,
+                    owner.display(),
+                    compose_path.display()
+                );
+                problems += 1;
+                outcome = outcome.combine(Validation::ConfigError);
+                continue;
+            }
+            owners.insert(container.name, compose_path.clone());
+        }
+    }
+
+    if problems == 0 {
+        println!("Configuration OK: {} containers", owners.len());
+    } else {
+        println!("{} problem(s) found", problems);
+    }
+    outcome
+}
+
+// =============================================================================
+// Discovery Dump
+// =============================================================================
+
+#[derive(Serialize)]
+struct FileDump {
+    path: String,
+    enabled: bool,
+    error: Option<String>,
+    services: Vec<ServiceDecision>,
+}
+
+#[derive(Serialize)]
+struct ListedContainer<'a> {
+    name: &'a str,
+    compose_file: String,
+}
+
+/// Runs discovery once, as the monitor does at startup, and returns every
+/// managed container with its compose file, sorted by name. Podman is only
+/// asked for container labels, with `label_matching`.
+pub fn list_containers(config: &Config) -> Result<Vec<(String, PathBuf)>> {
+    let labeled = if config.label_matching {
+        // Only `podman ps` runs, so the compose tool does not matter
+        let podman = PodmanClient::new(config.container_host()?, ComposeTool::Standalone)
+            .with_tls(config.remote_tls());
+        labeled_containers(&podman)
+    } else {
+        Vec::new()
+    };
+    let (specs, _) = discover(config, &labeled);
+    let mut containers: Vec<(String, PathBuf)> = specs
+        .into_iter()
+        .map(|(spec, compose_file)| (spec.name, compose_file))
+        .collect();
+    containers.sort();
+    Ok(containers)
+}
+
+/// Prints each discovered container and its compose file for
+/// `--list-containers`, one per line or as a JSON array.
+pub fn print_containers(containers: &[(String, PathBuf)], json: bool) -> Result<()> {
+    if json {
+        let listed: Vec<ListedContainer> = containers
+            .iter()
+            .map(|(name, compose_file)| ListedContainer {
+                name,
+                compose_file: compose_file.display().to_string(),
+            })
+            .collect();
+        println!("{}", to_json(&listed)?);
+        return Ok(());
+    }
+    let width = containers
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for (name, compose_file) in containers {
+        println!(
+            "{:<width$}  {}",
+            name,
+            compose_file.display(),
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Prints, for every compose file, each service the parser saw, the names it
+/// resolved, its restart policy and whether it was included (and why not).
+/// Returns whether every enabled file could be parsed.
+pub fn dump_discovery(config: &Config, json: bool) -> Result<bool> {
+    let files: Vec<FileDump> = config
+        .compose_files
+        .iter()
+        .map(|compose_file| {
+            let mut dump = FileDump {
+                path: compose_file.path.clone(),
+                enabled: compose_file.enabled,
+                error: None,
+                services: Vec::new(),
+            };
+            if compose_file.enabled {
+                let parse_options = config.file_parse_options(compose_file);
+                match ComposeParser::parse_file(compose_file.path.as_ref(), &parse_options) {
+                    Ok(parsed) => dump.services = parsed.decisions,
+                    Err(e) => dump.error = Some(format!("{:#}", e)),
+                }
+            }
+            dump
+        })
+        .collect();
+
+    let ok = files.iter().all(|file| file.error.is_none());
+    if json {
+        println!("{}", to_json(&files)?);
+        return Ok(ok);
+    }
+
+    for file in &files {
+        if !file.enabled {
+            println!("{} (disabled)", file.path);
+            continue;
+        }
+        if let Some(error) = &file.error {
+            println!("{}\n  error: {}", file.path, error);
+            continue;
+        }
+        println!("{}", file.path);
+        if file.services.is_empty() {
+            println!("  no services found");
+        }
+        for service in &file.services {
+            let verdict = if service.included {
+                format!("included as {}", service.container_names.join(", "))
+            } else {
+                "skipped".to_string()
+            };
+            println!(
+                "  {} (restart: {}): {}",
+                service.service, service.restart_policy, verdict
+            );
+            if let Some(declared) = &service.declared_name {
+                println!("      container_name: {}", declared);
+            }
+            if let Some(reason) = &service.reason {
+                println!("      {}", reason);
+            }
+        }
+    }
+    Ok(ok)
+}